@@ -0,0 +1,64 @@
+/// Classic libpcap files
+pub mod pcap;
+/// Pcapng files
+pub mod pcapng;
+
+/// Link layer type of a capture, as a `LINKTYPE_*` value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LinkType(pub u32);
+
+impl LinkType {
+    pub const NULL: LinkType = LinkType(0);
+    pub const ETHERNET: LinkType = LinkType(1);
+    pub const RAW: LinkType = LinkType(101);
+    pub const LOOP: LinkType = LinkType(108);
+    pub const LINUX_SLL: LinkType = LinkType(113);
+    pub const IPV4: LinkType = LinkType(228);
+    pub const IPV6: LinkType = LinkType(229);
+    pub const LINUX_SLL2: LinkType = LinkType(276);
+}
+
+fn read_u16(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes = bytes.get(offset..offset + 2)?.try_into().ok()?;
+    Some(match big_endian {
+        true => u16::from_be_bytes(bytes),
+        false => u16::from_le_bytes(bytes),
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + 4)?.try_into().ok()?;
+    Some(match big_endian {
+        true => u32::from_be_bytes(bytes),
+        false => u32::from_le_bytes(bytes),
+    })
+}
+
+/// Encodes integers in the byte order of a capture file
+trait ToBytes<const N: usize> {
+    fn to_be(self) -> [u8; N];
+    fn to_le(self) -> [u8; N];
+}
+
+macro_rules! impl_to_bytes {
+    ($($type:ty),*) => {
+        $(impl ToBytes<{ std::mem::size_of::<$type>() }> for $type {
+            fn to_be(self) -> [u8; std::mem::size_of::<$type>()] {
+                self.to_be_bytes()
+            }
+
+            fn to_le(self) -> [u8; std::mem::size_of::<$type>()] {
+                self.to_le_bytes()
+            }
+        })*
+    };
+}
+
+impl_to_bytes!(u16, u32, i64);
+
+fn to_bytes<const N: usize, T: ToBytes<N>>(value: T, big_endian: bool) -> [u8; N] {
+    match big_endian {
+        true => value.to_be(),
+        false => value.to_le(),
+    }
+}
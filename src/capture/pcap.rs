@@ -0,0 +1,210 @@
+use super::{read_u16, read_u32, to_bytes, LinkType};
+use crate::packet::Packet;
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+const MAGIC_MICROSECONDS: u32 = 0xa1b2c3d4;
+const MAGIC_NANOSECONDS: u32 = 0xa1b23c4d;
+const FILE_HEADER_LENGTH: usize = 24;
+const RECORD_HEADER_LENGTH: usize = 16;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("Invalid pcap magic {0:#010x}")]
+    InvalidMagic(u32),
+    #[error("Truncated pcap file header")]
+    TruncatedHeader,
+    #[error("Truncated pcap record at offset {0}")]
+    TruncatedRecord(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampResolution {
+    Microseconds,
+    Nanoseconds,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcapHeader {
+    pub big_endian: bool,
+    pub timestamp_resolution: TimestampResolution,
+    pub version_major: u16,
+    pub version_minor: u16,
+    pub snaplen: u32,
+    pub link_type: LinkType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcapRecord<'a> {
+    /// Time since the unix epoch
+    pub timestamp: Duration,
+    pub captured_length: u32,
+    /// Length of the packet on the wire, may be bigger than the captured data
+    pub original_length: u32,
+    pub data: &'a [u8],
+}
+
+/// Zero-copy reader of a classic pcap file, in any byte order and timestamp resolution.
+///
+/// Iterating yields the records, and stops after the first error.
+#[derive(Debug, Clone)]
+pub struct PcapReader<'a> {
+    header: PcapHeader,
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PcapReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        let magic = read_u32(bytes, 0, false).ok_or(ParseError::TruncatedHeader)?;
+        let (big_endian, timestamp_resolution) = match (magic, magic.swap_bytes()) {
+            (MAGIC_MICROSECONDS, _) => (false, TimestampResolution::Microseconds),
+            (MAGIC_NANOSECONDS, _) => (false, TimestampResolution::Nanoseconds),
+            (_, MAGIC_MICROSECONDS) => (true, TimestampResolution::Microseconds),
+            (_, MAGIC_NANOSECONDS) => (true, TimestampResolution::Nanoseconds),
+            _ => return Err(ParseError::InvalidMagic(magic)),
+        };
+        let field = |offset| read_u32(bytes, offset, big_endian).ok_or(ParseError::TruncatedHeader);
+        let header = PcapHeader {
+            big_endian,
+            timestamp_resolution,
+            version_major: read_u16(bytes, 4, big_endian).ok_or(ParseError::TruncatedHeader)?,
+            version_minor: read_u16(bytes, 6, big_endian).ok_or(ParseError::TruncatedHeader)?,
+            snaplen: field(16)?,
+            // the upper bits hold the FCS length, which isn't part of the link type
+            link_type: LinkType(field(20)? & 0x0fff_ffff),
+        };
+        Ok(Self {
+            header,
+            bytes,
+            offset: FILE_HEADER_LENGTH,
+        })
+    }
+
+    pub fn get_header(&self) -> &PcapHeader {
+        &self.header
+    }
+
+    pub fn get_link_type(&self) -> LinkType {
+        self.header.link_type
+    }
+
+    fn read_record(&self) -> Result<(PcapRecord<'a>, usize), ParseError> {
+        let big_endian = self.header.big_endian;
+        let truncated = ParseError::TruncatedRecord(self.offset);
+        let field = |index: usize| read_u32(self.bytes, self.offset + index * 4, big_endian);
+        let (Some(seconds), Some(fraction), Some(captured_length), Some(original_length)) =
+            (field(0), field(1), field(2), field(3))
+        else {
+            return Err(truncated);
+        };
+        let start = self.offset + RECORD_HEADER_LENGTH;
+        let end = start + captured_length as usize;
+        let data = self.bytes.get(start..end).ok_or(truncated)?;
+        let fraction = match self.header.timestamp_resolution {
+            TimestampResolution::Microseconds => Duration::from_micros(fraction.into()),
+            TimestampResolution::Nanoseconds => Duration::from_nanos(fraction.into()),
+        };
+        let record = PcapRecord {
+            timestamp: Duration::from_secs(seconds.into()) + fraction,
+            captured_length,
+            original_length,
+            data,
+        };
+        Ok((record, end))
+    }
+}
+
+impl<'a> Iterator for PcapReader<'a> {
+    type Item = Result<PcapRecord<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.bytes.len() {
+            return None;
+        }
+        Some(match self.read_record() {
+            Ok((record, end)) => {
+                self.offset = end;
+                Ok(record)
+            }
+            Err(error) => {
+                self.offset = self.bytes.len();
+                Err(error)
+            }
+        })
+    }
+}
+
+/// Writer of classic pcap files, with the byte order and timestamp resolution of `header`
+#[derive(Debug)]
+pub struct PcapWriter<W: Write> {
+    header: PcapHeader,
+    writer: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Writes the file header
+    pub fn new(mut writer: W, header: PcapHeader) -> io::Result<Self> {
+        let magic = match header.timestamp_resolution {
+            TimestampResolution::Microseconds => MAGIC_MICROSECONDS,
+            TimestampResolution::Nanoseconds => MAGIC_NANOSECONDS,
+        };
+        let big_endian = header.big_endian;
+        writer.write_all(&to_bytes(magic, big_endian))?;
+        writer.write_all(&to_bytes(header.version_major, big_endian))?;
+        writer.write_all(&to_bytes(header.version_minor, big_endian))?;
+        // thiszone and sigfigs, which are always 0
+        writer.write_all(&[0; 8])?;
+        writer.write_all(&to_bytes(header.snaplen, big_endian))?;
+        writer.write_all(&to_bytes(header.link_type.0, big_endian))?;
+        Ok(Self { header, writer })
+    }
+
+    pub fn get_header(&self) -> &PcapHeader {
+        &self.header
+    }
+
+    pub fn write_record(&mut self, record: &PcapRecord) -> io::Result<()> {
+        let seconds = u32::try_from(record.timestamp.as_secs()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Timestamp doesn't fit in 32 bits of seconds",
+            )
+        })?;
+        let fraction = match self.header.timestamp_resolution {
+            TimestampResolution::Microseconds => record.timestamp.subsec_micros(),
+            TimestampResolution::Nanoseconds => record.timestamp.subsec_nanos(),
+        };
+        let big_endian = self.header.big_endian;
+        self.writer.write_all(&to_bytes(seconds, big_endian))?;
+        self.writer.write_all(&to_bytes(fraction, big_endian))?;
+        self.writer
+            .write_all(&to_bytes(record.data.len() as u32, big_endian))?;
+        self.writer
+            .write_all(&to_bytes(record.original_length, big_endian))?;
+        self.writer.write_all(record.data)
+    }
+
+    /// Writes the bytes a packet was parsed from.
+    /// `original_length` is the length of the packet on the wire, before it was snapped
+    pub fn write_packet(
+        &mut self,
+        packet: &Packet,
+        timestamp: Duration,
+        original_length: u32,
+    ) -> io::Result<()> {
+        let data = packet.get_bytes();
+        self.write_record(&PcapRecord {
+            timestamp,
+            captured_length: data.len() as u32,
+            original_length,
+            data,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
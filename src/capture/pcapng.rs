@@ -0,0 +1,686 @@
+use super::{read_u16, read_u32, to_bytes, LinkType};
+use crate::packet::Packet;
+use smallvec::SmallVec;
+use std::{
+    io::{self, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d0d0a;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001;
+const OBSOLETE_PACKET_BLOCK: u32 = 0x00000002;
+const SIMPLE_PACKET_BLOCK: u32 = 0x00000003;
+const NAME_RESOLUTION_BLOCK: u32 = 0x00000004;
+const ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+const BLOCK_HEADER_LENGTH: usize = 8;
+const BLOCK_TRAILER_LENGTH: usize = 4;
+
+pub const OPTION_END: u16 = 0;
+pub const OPTION_COMMENT: u16 = 1;
+pub const OPTION_INTERFACE_NAME: u16 = 2;
+pub const OPTION_INTERFACE_TIMESTAMP_RESOLUTION: u16 = 9;
+pub const OPTION_INTERFACE_TIMESTAMP_OFFSET: u16 = 14;
+
+const NAME_RECORD_END: u16 = 0;
+const NAME_RECORD_IPV4: u16 = 1;
+const NAME_RECORD_IPV6: u16 = 2;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("File doesn't start with a section header block")]
+    NotAPcapng,
+    #[error("Invalid byte order magic {0:#010x}")]
+    InvalidByteOrderMagic(u32),
+    #[error("Truncated block at offset {0}")]
+    TruncatedBlock(usize),
+    #[error("Invalid block length at offset {0}")]
+    InvalidBlockLength(usize),
+    #[error("Packet of an undeclared interface {0}")]
+    UnknownInterface(u32),
+}
+
+fn round_up_to_4(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcapngOption<'a> {
+    pub code: u16,
+    pub value: &'a [u8],
+}
+
+/// Zero-copy iterator over the options of a block.
+/// Stops at the end of options, or at the first truncated option
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcapngOptionsIterator<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> PcapngOptionsIterator<'a> {
+    pub fn new(bytes: &'a [u8], big_endian: bool) -> Self {
+        Self { bytes, big_endian }
+    }
+
+    /// Value of the first option with `code`
+    pub fn get(&self, code: u16) -> Option<&'a [u8]> {
+        self.clone()
+            .find(|option| option.code == code)
+            .map(|option| option.value)
+    }
+
+    /// Comments (`opt_comment`) attached to the block
+    pub fn get_comments(&self) -> impl Iterator<Item = &'a str> {
+        self.clone()
+            .filter(|option| option.code == OPTION_COMMENT)
+            .filter_map(|option| std::str::from_utf8(option.value).ok())
+    }
+}
+
+impl<'a> Iterator for PcapngOptionsIterator<'a> {
+    type Item = PcapngOption<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let code = read_u16(self.bytes, 0, self.big_endian)?;
+        let length = read_u16(self.bytes, 2, self.big_endian)? as usize;
+        if code == OPTION_END {
+            return None;
+        }
+        let value = self.bytes.get(4..4 + length)?;
+        self.bytes = self
+            .bytes
+            .get(4 + round_up_to_4(length)..)
+            .unwrap_or_default();
+        Some(PcapngOption { code, value })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionHeader<'a> {
+    pub big_endian: bool,
+    pub version_major: u16,
+    pub version_minor: u16,
+    /// Length of the section, -1 if it isn't specified
+    pub section_length: i64,
+    pub options: PcapngOptionsIterator<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface<'a> {
+    pub link_type: LinkType,
+    pub snaplen: u32,
+    /// Timestamp units in a second (`if_tsresol`), microseconds by default
+    pub units_per_second: u64,
+    /// Seconds added to the timestamps (`if_tsoffset`)
+    pub timestamp_offset: i64,
+    pub options: PcapngOptionsIterator<'a>,
+}
+
+impl<'a> Interface<'a> {
+    pub fn get_name(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.options.get(OPTION_INTERFACE_NAME)?).ok()
+    }
+
+    /// Converts a timestamp in this interface's units to the time since the unix epoch
+    pub fn get_timestamp(&self, units: u64) -> Duration {
+        let nanoseconds =
+            (units % self.units_per_second) as u128 * 1_000_000_000 / self.units_per_second as u128;
+        let timestamp = Duration::from_secs(units / self.units_per_second)
+            + Duration::from_nanos(nanoseconds as u64);
+        let offset = Duration::from_secs(self.timestamp_offset.unsigned_abs());
+        match self.timestamp_offset < 0 {
+            true => timestamp.saturating_sub(offset),
+            false => timestamp.saturating_add(offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcapngPacket<'a> {
+    pub interface_id: u32,
+    pub link_type: LinkType,
+    /// Time since the unix epoch, simple packet blocks don't have one
+    pub timestamp: Option<Duration>,
+    pub captured_length: u32,
+    /// Length of the packet on the wire, may be bigger than the captured data
+    pub original_length: u32,
+    pub data: &'a [u8],
+    pub options: PcapngOptionsIterator<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameRecord<'a> {
+    pub address: IpAddr,
+    names: &'a [u8],
+}
+
+impl<'a> NameRecord<'a> {
+    pub fn get_names(&self) -> impl Iterator<Item = &'a str> {
+        self.names
+            .split(|byte| *byte == 0)
+            .filter(|name| !name.is_empty())
+            .filter_map(|name| std::str::from_utf8(name).ok())
+    }
+}
+
+/// Zero-copy iterator over the records of a name resolution block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameRecordsIterator<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> NameRecordsIterator<'a> {
+    /// Bytes after the end of the records
+    fn remainder(&self) -> &'a [u8] {
+        let mut records = self.clone();
+        while records.next_raw().is_some() {}
+        records.bytes
+    }
+
+    fn next_raw(&mut self) -> Option<(u16, &'a [u8])> {
+        let kind = read_u16(self.bytes, 0, self.big_endian)?;
+        let length = read_u16(self.bytes, 2, self.big_endian)? as usize;
+        let value = self.bytes.get(4..4 + length)?;
+        self.bytes = self
+            .bytes
+            .get(4 + round_up_to_4(length)..)
+            .unwrap_or_default();
+        match kind {
+            NAME_RECORD_END => None,
+            _ => Some((kind, value)),
+        }
+    }
+}
+
+impl<'a> Iterator for NameRecordsIterator<'a> {
+    type Item = NameRecord<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (kind, value) = self.next_raw()?;
+            let (address, names) = match kind {
+                NAME_RECORD_IPV4 if value.len() >= 4 => {
+                    let octets: [u8; 4] = value[..4].try_into().ok()?;
+                    (Ipv4Addr::from(octets).into(), &value[4..])
+                }
+                NAME_RECORD_IPV6 if value.len() >= 16 => {
+                    let octets: [u8; 16] = value[..16].try_into().ok()?;
+                    (Ipv6Addr::from(octets).into(), &value[16..])
+                }
+                // other record types (like EUI addresses) are skipped
+                _ => continue,
+            };
+            return Some(NameRecord { address, names });
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameResolution<'a> {
+    pub records: NameRecordsIterator<'a>,
+    pub options: PcapngOptionsIterator<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block<'a> {
+    SectionHeader(SectionHeader<'a>),
+    InterfaceDescription(Interface<'a>),
+    /// Enhanced, simple, or obsolete packet block
+    Packet(PcapngPacket<'a>),
+    NameResolution(NameResolution<'a>),
+    /// Block type and body of the blocks that aren't parsed (statistics, journal, etc)
+    Unknown(u32, &'a [u8]),
+}
+
+/// Zero-copy reader of a pcapng file.
+///
+/// Iterating yields the blocks of all the sections, and stops after the first error.
+/// The interfaces of the current section are kept, so every packet gets its link type and
+/// timestamp resolution.
+#[derive(Debug, Clone)]
+pub struct PcapngReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    big_endian: bool,
+    interfaces: SmallVec<[Interface<'a>; 4]>,
+}
+
+impl<'a> PcapngReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        match read_u32(bytes, 0, false) {
+            Some(SECTION_HEADER_BLOCK) => Ok(Self {
+                bytes,
+                offset: 0,
+                big_endian: false,
+                interfaces: SmallVec::new(),
+            }),
+            _ => Err(ParseError::NotAPcapng),
+        }
+    }
+
+    /// Interfaces declared so far in the current section, by their id
+    pub fn get_interfaces(&self) -> &[Interface<'a>] {
+        &self.interfaces
+    }
+
+    /// Iterates only over the packets of the file
+    pub fn packets(self) -> impl Iterator<Item = Result<PcapngPacket<'a>, ParseError>> {
+        self.filter_map(|block| match block {
+            Ok(Block::Packet(packet)) => Some(Ok(packet)),
+            Ok(_) => None,
+            Err(error) => Some(Err(error)),
+        })
+    }
+
+    fn read_block(&mut self) -> Result<(Block<'a>, usize), ParseError> {
+        let offset = self.offset;
+        let block_type =
+            read_u32(self.bytes, offset, false).ok_or(ParseError::TruncatedBlock(offset))?;
+        if block_type == SECTION_HEADER_BLOCK {
+            let magic = read_u32(self.bytes, offset + BLOCK_HEADER_LENGTH, false)
+                .ok_or(ParseError::TruncatedBlock(offset))?;
+            self.big_endian = match magic {
+                BYTE_ORDER_MAGIC => false,
+                _ if magic.swap_bytes() == BYTE_ORDER_MAGIC => true,
+                _ => return Err(ParseError::InvalidByteOrderMagic(magic)),
+            };
+        }
+        let big_endian = self.big_endian;
+        let block_type = read_u32(self.bytes, offset, big_endian).unwrap_or(block_type);
+        let length = read_u32(self.bytes, offset + 4, big_endian)
+            .ok_or(ParseError::TruncatedBlock(offset))?;
+        let length = length as usize;
+        if !length.is_multiple_of(4) || length < BLOCK_HEADER_LENGTH + BLOCK_TRAILER_LENGTH {
+            return Err(ParseError::InvalidBlockLength(offset));
+        }
+        let block = self
+            .bytes
+            .get(offset..offset + length)
+            .ok_or(ParseError::TruncatedBlock(offset))?;
+        if read_u32(block, length - BLOCK_TRAILER_LENGTH, big_endian) != Some(length as u32) {
+            return Err(ParseError::InvalidBlockLength(offset));
+        }
+        let body = &block[BLOCK_HEADER_LENGTH..length - BLOCK_TRAILER_LENGTH];
+        let truncated = ParseError::TruncatedBlock(offset);
+        let options = |start: usize| {
+            PcapngOptionsIterator::new(body.get(start..).unwrap_or_default(), big_endian)
+        };
+
+        let block = match block_type {
+            SECTION_HEADER_BLOCK => {
+                self.interfaces.clear();
+                let section_length_bytes = body.get(8..16).ok_or(truncated)?.try_into().unwrap();
+                Block::SectionHeader(SectionHeader {
+                    big_endian,
+                    version_major: read_u16(body, 4, big_endian).ok_or(truncated)?,
+                    version_minor: read_u16(body, 6, big_endian).ok_or(truncated)?,
+                    section_length: match big_endian {
+                        true => i64::from_be_bytes(section_length_bytes),
+                        false => i64::from_le_bytes(section_length_bytes),
+                    },
+                    options: options(16),
+                })
+            }
+            INTERFACE_DESCRIPTION_BLOCK => {
+                let options = options(8);
+                let interface = Interface {
+                    link_type: LinkType(read_u16(body, 0, big_endian).ok_or(truncated)?.into()),
+                    snaplen: read_u32(body, 4, big_endian).ok_or(truncated)?,
+                    units_per_second: options
+                        .get(OPTION_INTERFACE_TIMESTAMP_RESOLUTION)
+                        .and_then(|value| units_per_second(*value.first()?))
+                        .unwrap_or(1_000_000),
+                    timestamp_offset: options
+                        .get(OPTION_INTERFACE_TIMESTAMP_OFFSET)
+                        .and_then(|value| value.try_into().ok())
+                        .map(|value| match big_endian {
+                            true => i64::from_be_bytes(value),
+                            false => i64::from_le_bytes(value),
+                        })
+                        .unwrap_or(0),
+                    options,
+                };
+                self.interfaces.push(interface.clone());
+                Block::InterfaceDescription(interface)
+            }
+            ENHANCED_PACKET_BLOCK | OBSOLETE_PACKET_BLOCK => {
+                let interface_id = match block_type {
+                    ENHANCED_PACKET_BLOCK => read_u32(body, 0, big_endian),
+                    _ => read_u16(body, 0, big_endian).map(u32::from),
+                }
+                .ok_or(truncated)?;
+                let field = |index: usize| read_u32(body, 4 + index * 4, big_endian);
+                let (Some(high), Some(low), Some(captured_length), Some(original_length)) =
+                    (field(0), field(1), field(2), field(3))
+                else {
+                    return Err(ParseError::TruncatedBlock(offset));
+                };
+                let interface = self.get_interface(interface_id)?;
+                let data = body
+                    .get(20..20 + captured_length as usize)
+                    .ok_or(ParseError::TruncatedBlock(offset))?;
+                Block::Packet(PcapngPacket {
+                    interface_id,
+                    link_type: interface.link_type,
+                    timestamp: Some(interface.get_timestamp((high as u64) << 32 | low as u64)),
+                    captured_length,
+                    original_length,
+                    data,
+                    options: options(20 + round_up_to_4(captured_length as usize)),
+                })
+            }
+            SIMPLE_PACKET_BLOCK => {
+                let original_length = read_u32(body, 0, big_endian).ok_or(truncated)?;
+                let interface = self.get_interface(0)?;
+                // the captured length is implied by the block length, without its padding
+                let mut captured_length = (body.len() - 4).min(original_length as usize);
+                if interface.snaplen != 0 {
+                    captured_length = captured_length.min(interface.snaplen as usize);
+                }
+                Block::Packet(PcapngPacket {
+                    interface_id: 0,
+                    link_type: interface.link_type,
+                    timestamp: None,
+                    captured_length: captured_length as u32,
+                    original_length,
+                    data: &body[4..4 + captured_length],
+                    options: PcapngOptionsIterator::new(&[], big_endian),
+                })
+            }
+            NAME_RESOLUTION_BLOCK => {
+                let records = NameRecordsIterator {
+                    bytes: body,
+                    big_endian,
+                };
+                Block::NameResolution(NameResolution {
+                    options: PcapngOptionsIterator::new(records.remainder(), big_endian),
+                    records,
+                })
+            }
+            _ => Block::Unknown(block_type, body),
+        };
+        Ok((block, offset + length))
+    }
+
+    fn get_interface(&self, interface_id: u32) -> Result<&Interface<'a>, ParseError> {
+        self.interfaces
+            .get(interface_id as usize)
+            .ok_or(ParseError::UnknownInterface(interface_id))
+    }
+}
+
+/// Parses `if_tsresol`: the exponent of a negative power of 10, or of 2 if the msb is set
+fn units_per_second(resolution: u8) -> Option<u64> {
+    match resolution & 0x80 {
+        0 => 10u64.checked_pow(resolution.into()),
+        _ => 1u64.checked_shl((resolution & 0x7f).into()),
+    }
+}
+
+impl<'a> Iterator for PcapngReader<'a> {
+    type Item = Result<Block<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.bytes.len() {
+            return None;
+        }
+        Some(match self.read_block() {
+            Ok((block, end)) => {
+                self.offset = end;
+                Ok(block)
+            }
+            Err(error) => {
+                self.offset = self.bytes.len();
+                Err(error)
+            }
+        })
+    }
+}
+
+/// Writer of pcapng files.
+///
+/// Blocks read by a `PcapngReader` can be written back with `write_block`, so filtered captures
+/// keep their interfaces, comments and name resolution.
+#[derive(Debug)]
+pub struct PcapngWriter<W: Write> {
+    writer: W,
+    big_endian: bool,
+    /// Link type, timestamp units in a second and offset of the interfaces of the current section
+    interfaces: SmallVec<[(LinkType, u64, i64); 4]>,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Starts a little endian section, without options
+    pub fn new(writer: W) -> io::Result<Self> {
+        Self::with_section(
+            writer,
+            &SectionHeader {
+                big_endian: false,
+                version_major: 1,
+                version_minor: 0,
+                section_length: -1,
+                options: PcapngOptionsIterator::new(&[], false),
+            },
+        )
+    }
+
+    pub fn with_section(writer: W, section: &SectionHeader) -> io::Result<Self> {
+        let mut pcapng_writer = Self {
+            writer,
+            big_endian: section.big_endian,
+            interfaces: SmallVec::new(),
+        };
+        pcapng_writer.write_section_header(section)?;
+        Ok(pcapng_writer)
+    }
+
+    /// Starts a new section, with its own byte order and interfaces
+    pub fn write_section_header(&mut self, section: &SectionHeader) -> io::Result<()> {
+        self.big_endian = section.big_endian;
+        self.interfaces.clear();
+        let mut body = to_bytes(BYTE_ORDER_MAGIC, self.big_endian).to_vec();
+        body.extend_from_slice(&to_bytes(section.version_major, self.big_endian));
+        body.extend_from_slice(&to_bytes(section.version_minor, self.big_endian));
+        body.extend_from_slice(&to_bytes(section.section_length, self.big_endian));
+        self.encode_options(&mut body, section.options.clone());
+        self.write_raw_block(SECTION_HEADER_BLOCK, &body)
+    }
+
+    /// Declares an interface, and returns its id.
+    /// The timestamp resolution and offset are added to the options if they are missing
+    pub fn write_interface(&mut self, interface: &Interface) -> io::Result<u32> {
+        let mut body = to_bytes(interface.link_type.0 as u16, self.big_endian).to_vec();
+        body.extend_from_slice(&[0, 0]);
+        body.extend_from_slice(&to_bytes(interface.snaplen, self.big_endian));
+        let mut extra_options = Vec::new();
+        if interface.units_per_second != 1_000_000
+            && interface
+                .options
+                .get(OPTION_INTERFACE_TIMESTAMP_RESOLUTION)
+                .is_none()
+        {
+            let resolution = timestamp_resolution(interface.units_per_second).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Timestamp resolution isn't a power of 10 or 2",
+                )
+            })?;
+            extra_options.push((OPTION_INTERFACE_TIMESTAMP_RESOLUTION, vec![resolution]));
+        }
+        if interface.timestamp_offset != 0
+            && interface
+                .options
+                .get(OPTION_INTERFACE_TIMESTAMP_OFFSET)
+                .is_none()
+        {
+            let offset = to_bytes(interface.timestamp_offset, self.big_endian);
+            extra_options.push((OPTION_INTERFACE_TIMESTAMP_OFFSET, offset.to_vec()));
+        }
+        let extra_options = extra_options
+            .iter()
+            .map(|(code, value)| PcapngOption { code: *code, value });
+        self.encode_options(&mut body, interface.options.clone().chain(extra_options));
+        self.write_raw_block(INTERFACE_DESCRIPTION_BLOCK, &body)?;
+
+        self.interfaces.push((
+            interface.link_type,
+            interface.units_per_second,
+            interface.timestamp_offset,
+        ));
+        Ok(self.interfaces.len() as u32 - 1)
+    }
+
+    /// Declares an interface with microseconds timestamps, and returns its id
+    pub fn add_interface(&mut self, link_type: LinkType, snaplen: u32) -> io::Result<u32> {
+        self.write_interface(&Interface {
+            link_type,
+            snaplen,
+            units_per_second: 1_000_000,
+            timestamp_offset: 0,
+            options: PcapngOptionsIterator::new(&[], self.big_endian),
+        })
+    }
+
+    /// Writes an enhanced packet block, or a simple packet block if the packet has no timestamp
+    /// and belongs to the first interface (simple packet blocks have no interface id).
+    /// Packets of other interfaces without a timestamp are written with a zero timestamp
+    pub fn write_packet(&mut self, packet: &PcapngPacket) -> io::Result<()> {
+        let (_, units_per_second, timestamp_offset) = self.get_interface(packet.interface_id)?;
+        let big_endian = self.big_endian;
+        let timestamp = match packet.timestamp {
+            Some(timestamp) => timestamp,
+            None if packet.interface_id == 0 => {
+                let mut body = to_bytes(packet.original_length, big_endian).to_vec();
+                body.extend_from_slice(packet.data);
+                body.resize(round_up_to_4(body.len()), 0);
+                return self.write_raw_block(SIMPLE_PACKET_BLOCK, &body);
+            }
+            None => Duration::ZERO,
+        };
+
+        let offset = Duration::from_secs(timestamp_offset.unsigned_abs());
+        let timestamp = match timestamp_offset < 0 {
+            true => timestamp.saturating_add(offset),
+            false => timestamp.saturating_sub(offset),
+        };
+        let units = timestamp.as_nanos() * units_per_second as u128 / 1_000_000_000;
+        let units = u64::try_from(units).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Timestamp doesn't fit in the interface's units",
+            )
+        })?;
+        let mut body = to_bytes(packet.interface_id, big_endian).to_vec();
+        body.extend_from_slice(&to_bytes((units >> 32) as u32, big_endian));
+        body.extend_from_slice(&to_bytes(units as u32, big_endian));
+        body.extend_from_slice(&to_bytes(packet.data.len() as u32, big_endian));
+        body.extend_from_slice(&to_bytes(packet.original_length, big_endian));
+        body.extend_from_slice(packet.data);
+        body.resize(round_up_to_4(body.len()), 0);
+        self.encode_options(&mut body, packet.options.clone());
+        self.write_raw_block(ENHANCED_PACKET_BLOCK, &body)
+    }
+
+    /// Writes the bytes a packet was parsed from, with the link type of its interface.
+    /// `original_length` is the length of the packet on the wire, before it was snapped
+    pub fn write_parsed_packet(
+        &mut self,
+        interface_id: u32,
+        packet: &Packet,
+        timestamp: Duration,
+        original_length: u32,
+    ) -> io::Result<()> {
+        let (link_type, _, _) = self.get_interface(interface_id)?;
+        let data = packet.get_bytes();
+        self.write_packet(&PcapngPacket {
+            interface_id,
+            link_type,
+            timestamp: Some(timestamp),
+            captured_length: data.len() as u32,
+            original_length,
+            data,
+            options: PcapngOptionsIterator::new(&[], self.big_endian),
+        })
+    }
+
+    pub fn write_name_resolution(&mut self, names: &NameResolution) -> io::Result<()> {
+        let mut body = Vec::new();
+        let mut records = names.records.clone();
+        while let Some((kind, value)) = records.next_raw() {
+            body.extend_from_slice(&to_bytes(kind, self.big_endian));
+            body.extend_from_slice(&to_bytes(value.len() as u16, self.big_endian));
+            body.extend_from_slice(value);
+            body.resize(round_up_to_4(body.len()), 0);
+        }
+        body.extend_from_slice(&[0; 4]);
+        self.encode_options(&mut body, names.options.clone());
+        self.write_raw_block(NAME_RESOLUTION_BLOCK, &body)
+    }
+
+    /// Writes any block read by a `PcapngReader`
+    pub fn write_block(&mut self, block: &Block) -> io::Result<()> {
+        match block {
+            Block::SectionHeader(section) => self.write_section_header(section),
+            Block::InterfaceDescription(interface) => self.write_interface(interface).map(drop),
+            Block::Packet(packet) => self.write_packet(packet),
+            Block::NameResolution(names) => self.write_name_resolution(names),
+            Block::Unknown(block_type, body) => self.write_raw_block(*block_type, body),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn get_interface(&self, interface_id: u32) -> io::Result<(LinkType, u64, i64)> {
+        self.interfaces
+            .get(interface_id as usize)
+            .copied()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Packet of an undeclared interface",
+                )
+            })
+    }
+
+    fn encode_options<'a>(
+        &self,
+        body: &mut Vec<u8>,
+        options: impl Iterator<Item = PcapngOption<'a>>,
+    ) {
+        let length = body.len();
+        for option in options {
+            body.extend_from_slice(&to_bytes(option.code, self.big_endian));
+            body.extend_from_slice(&to_bytes(option.value.len() as u16, self.big_endian));
+            body.extend_from_slice(option.value);
+            body.resize(round_up_to_4(body.len()), 0);
+        }
+        if body.len() != length {
+            body.extend_from_slice(&[0; 4]);
+        }
+    }
+
+    fn write_raw_block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        let length = to_bytes(
+            (BLOCK_HEADER_LENGTH + body.len() + BLOCK_TRAILER_LENGTH) as u32,
+            self.big_endian,
+        );
+        self.writer
+            .write_all(&to_bytes(block_type, self.big_endian))?;
+        self.writer.write_all(&length)?;
+        self.writer.write_all(body)?;
+        self.writer.write_all(&length)
+    }
+}
+
+/// Encodes `if_tsresol`, the inverse of `units_per_second`
+fn timestamp_resolution(units_per_second: u64) -> Option<u8> {
+    match units_per_second {
+        units if units.is_power_of_two() => Some(0x80 | units.trailing_zeros() as u8),
+        units => (0..20u8).find(|exponent| 10u64.checked_pow((*exponent).into()) == Some(units)),
+    }
+}
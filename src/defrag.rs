@@ -0,0 +1,416 @@
+use crate::{
+    l2::L2Packet,
+    l3::L3Packet,
+    packet::{HeaderPosition, Packet},
+};
+use pnet::packet::{
+    ip::IpNextHeaderProtocol,
+    ipv4::{self, MutableIpv4Packet},
+    Packet as _,
+};
+use std::{collections::HashMap, net::IpAddr, time::Duration};
+
+/// Maximum value of the IP length fields, which are 16 bits
+const MAX_IP_LENGTH: usize = u16::MAX as usize;
+const IPV6_HEADER_LENGTH: usize = 40;
+const IPV6_FRAGMENT_HEADER_LENGTH: usize = 8;
+const IPV6_NEXT_HEADER_OFFSET: usize = 6;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum DefragError {
+    #[error("Packet is not an IP fragment")]
+    NotAFragment,
+    #[error("Fragment is beyond the maximum IP packet size")]
+    FragmentTooLarge,
+    #[error("Fragment doesn't fit in the memory limit")]
+    MemoryLimit,
+    #[error("Fragment length conflicts with the previous fragments")]
+    InconsistentLength,
+}
+
+/// Which data is kept when fragments overlap.
+///
+/// Operating systems resolve overlaps differently, and an IDS has to pick the policy
+/// of the host it protects, otherwise overlapping fragments can be used for evasion.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// The data that arrived first is kept (Windows, Solaris)
+    First,
+    /// The data that arrived last is kept (some routers and printers)
+    Last,
+    /// The data that arrived first is kept, unless the new fragment starts before it
+    Bsd,
+    /// Same as `Bsd`, but a new fragment that starts at the same offset replaces the old one
+    #[default]
+    Linux,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefragConfig {
+    /// Time since the first fragment after which an incomplete packet is dropped
+    pub timeout: Duration,
+    /// Maximum amount of fragment bytes buffered, across all the packets.
+    /// When a new fragment doesn't fit, the oldest incomplete packets are dropped
+    pub max_memory: usize,
+    pub overlap_policy: OverlapPolicy,
+}
+
+impl Default for DefragConfig {
+    /// Same defaults as the linux kernel
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_memory: 4 * 1024 * 1024,
+            overlap_policy: OverlapPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FragmentKey {
+    source: IpAddr,
+    destination: IpAddr,
+    identification: u32,
+    /// Only in IPv4, IPv6 fragments are identified without it (RFC 8200),
+    /// since the first fragment may have more extension headers before the L4
+    protocol: Option<IpNextHeaderProtocol>,
+}
+
+#[derive(Debug)]
+struct Fragment {
+    offset: usize,
+    data: Vec<u8>,
+    arrival: u64,
+}
+
+impl Fragment {
+    fn end(&self) -> usize {
+        self.offset + self.data.len()
+    }
+}
+
+/// Headers of the first fragment, that are copied to the reassembled packet
+#[derive(Debug)]
+struct FirstHeaders {
+    /// L2 header, followed by the unfragmentable L3 headers
+    bytes: Vec<u8>,
+    /// Where the L3 header starts in `bytes`
+    l3_offset: usize,
+    /// IPv6 only - where the next header field that pointed to the fragment header is in `bytes`,
+    /// and the value that should replace it
+    next_header: Option<(usize, IpNextHeaderProtocol)>,
+}
+
+impl FirstHeaders {
+    /// Maximum length of the reassembled payload, so it still fits in the IP length field.
+    /// The IPv4 total length counts the header, and the IPv6 payload length counts the
+    /// unfragmentable extensions
+    fn max_payload_length(&self) -> usize {
+        let l3_headers_length = self.bytes.len() - self.l3_offset;
+        match self.next_header {
+            None => MAX_IP_LENGTH - l3_headers_length,
+            Some(_) => MAX_IP_LENGTH - (l3_headers_length - IPV6_HEADER_LENGTH),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Datagram {
+    headers: Option<FirstHeaders>,
+    fragments: Vec<Fragment>,
+    total_length: Option<usize>,
+    first_seen: Duration,
+    memory: usize,
+}
+
+impl Datagram {
+    fn is_complete(&self) -> bool {
+        let (Some(total_length), Some(_)) = (self.total_length, &self.headers) else {
+            return false;
+        };
+        let mut ranges: Vec<_> = self.fragments.iter().map(|f| (f.offset, f.end())).collect();
+        ranges.sort_unstable();
+        let mut covered = 0;
+        for (start, end) in ranges {
+            if start > covered {
+                return false;
+            }
+            covered = covered.max(end);
+        }
+        covered >= total_length
+    }
+
+    /// Lays the fragments one on top of the other, so the data kept in overlaps is the data of
+    /// the fragment that the policy favors
+    fn assemble_payload(&mut self, policy: OverlapPolicy) -> Vec<u8> {
+        let total_length = self.total_length.unwrap_or_default();
+        // sort from the least favored fragment to the most favored one
+        match policy {
+            OverlapPolicy::First => self.fragments.sort_by_key(|f| u64::MAX - f.arrival),
+            OverlapPolicy::Last => self.fragments.sort_by_key(|f| f.arrival),
+            OverlapPolicy::Bsd => self
+                .fragments
+                .sort_by_key(|f| (usize::MAX - f.offset, u64::MAX - f.arrival)),
+            OverlapPolicy::Linux => self
+                .fragments
+                .sort_by_key(|f| (usize::MAX - f.offset, f.arrival)),
+        }
+
+        let mut payload = vec![0; total_length];
+        for fragment in &self.fragments {
+            if fragment.offset >= total_length {
+                continue;
+            }
+            let end = fragment.end().min(total_length);
+            payload[fragment.offset..end].copy_from_slice(&fragment.data[..end - fragment.offset]);
+        }
+        payload
+    }
+}
+
+/// Reassembles fragmented IPv4 and IPv6 packets.
+///
+/// Every fragment is copied until its packet is complete, and then an owned packet is
+/// returned, with the L2 header of the first fragment. The returned packet isn't a fragment,
+/// and can be parsed again with `Packet::try_from`, or with `Packet::from_ip` if the fragments
+/// were in a level without an L2 header (raw IP, or an IP in IP tunnel).
+/// <br>
+/// Time is taken from the caller (the capture timestamps), so captures can be replayed.
+#[derive(Debug, Default)]
+pub struct Defragmenter {
+    config: DefragConfig,
+    datagrams: HashMap<FragmentKey, Datagram>,
+    memory: usize,
+    arrivals: u64,
+}
+
+impl Defragmenter {
+    pub fn new(config: DefragConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Adds the fragment at `position` of `packet`,
+    /// and returns the reassembled packet if it is now complete
+    pub fn process(
+        &mut self,
+        packet: &Packet,
+        position: HeaderPosition,
+        timestamp: Duration,
+    ) -> Result<Option<Vec<u8>>, DefragError> {
+        self.expire(timestamp);
+
+        let level = packet
+            .get_level(position)
+            .ok_or(DefragError::NotAFragment)?;
+        let l3 = level.get_l3().ok_or(DefragError::NotAFragment)?;
+        if !l3.is_fragment() {
+            return Err(DefragError::NotAFragment);
+        }
+        let key = FragmentKey {
+            source: l3.get_source().ok_or(DefragError::NotAFragment)?,
+            destination: l3.get_destination().ok_or(DefragError::NotAFragment)?,
+            identification: l3
+                .fragment_identification()
+                .ok_or(DefragError::NotAFragment)?,
+            protocol: match l3 {
+                L3Packet::Ipv4(ip, _) => Some(ip.get_next_level_protocol()),
+                _ => None,
+            },
+        };
+        let offset = l3.fragment_offset().unwrap_or_default() as usize;
+        let (data, headers) =
+            split_fragment(level.get_l2(), l3).ok_or(DefragError::NotAFragment)?;
+        if offset + data.len() > headers.max_payload_length() {
+            return Err(DefragError::FragmentTooLarge);
+        }
+        if data.len() > self.config.max_memory {
+            return Err(DefragError::MemoryLimit);
+        }
+        while self.memory + data.len() > self.config.max_memory {
+            if !self.drop_oldest(&key) {
+                return Err(DefragError::MemoryLimit);
+            }
+        }
+
+        let datagram = self
+            .datagrams
+            .entry(key.clone())
+            .or_insert_with(|| Datagram {
+                first_seen: timestamp,
+                ..Default::default()
+            });
+        if !l3.more_fragments() {
+            let total_length = offset + data.len();
+            if datagram
+                .total_length
+                .is_some_and(|length| length != total_length)
+            {
+                self.remove(&key);
+                return Err(DefragError::InconsistentLength);
+            }
+            datagram.total_length = Some(total_length);
+        }
+        if offset == 0 && datagram.headers.is_none() {
+            datagram.headers = Some(headers);
+        }
+        datagram.memory += data.len();
+        self.memory += data.len();
+        self.arrivals += 1;
+        datagram.fragments.push(Fragment {
+            offset,
+            data: data.to_vec(),
+            arrival: self.arrivals,
+        });
+
+        if !datagram.is_complete() {
+            return Ok(None);
+        }
+        let mut datagram = self.remove(&key).expect("datagram was just inserted");
+        // the headers of the first fragment may be longer than the headers of the others
+        let headers = datagram
+            .headers
+            .as_ref()
+            .expect("complete datagram has headers");
+        if datagram.total_length.unwrap_or_default() > headers.max_payload_length() {
+            return Err(DefragError::FragmentTooLarge);
+        }
+        Ok(Some(reassemble(&mut datagram, self.config.overlap_policy)))
+    }
+
+    /// Drops the incomplete packets that timed out, and returns their amount
+    pub fn expire(&mut self, now: Duration) -> usize {
+        let timeout = self.config.timeout;
+        let before = self.datagrams.len();
+        self.datagrams
+            .retain(|_, datagram| now.saturating_sub(datagram.first_seen) < timeout);
+        self.memory = self
+            .datagrams
+            .values()
+            .map(|datagram| datagram.memory)
+            .sum();
+        before - self.datagrams.len()
+    }
+
+    /// Amount of fragment bytes currently buffered
+    pub fn memory_usage(&self) -> usize {
+        self.memory
+    }
+
+    /// Amount of incomplete packets currently buffered
+    pub fn pending(&self) -> usize {
+        self.datagrams.len()
+    }
+
+    fn remove(&mut self, key: &FragmentKey) -> Option<Datagram> {
+        let datagram = self.datagrams.remove(key)?;
+        self.memory -= datagram.memory;
+        Some(datagram)
+    }
+
+    /// Drops the oldest incomplete packet, other than the one of `current`.
+    /// Returns whether a packet was dropped
+    fn drop_oldest(&mut self, current: &FragmentKey) -> bool {
+        let oldest = self
+            .datagrams
+            .iter()
+            .filter(|(key, _)| *key != current)
+            .min_by_key(|(_, datagram)| datagram.first_seen)
+            .map(|(key, _)| key.clone());
+        oldest.is_some_and(|key| self.remove(&key).is_some())
+    }
+}
+
+/// Splits a fragment to its data and the headers that will be used if it is the first fragment
+fn split_fragment<'a>(
+    l2: Option<&'a L2Packet>,
+    l3: &'a L3Packet,
+) -> Option<(&'a [u8], FirstHeaders)> {
+    let l2_header = l2.map_or([].as_slice(), |l2| l2.get_header());
+    Some(match l3 {
+        L3Packet::Ipv4(ip, _) => {
+            let header_length = ip.get_header_length() as usize * 4;
+            let bytes = ip.packet();
+            let mut headers = l2_header.to_vec();
+            headers.extend_from_slice(bytes.get(..header_length)?);
+            let headers = FirstHeaders {
+                bytes: headers,
+                l3_offset: l2_header.len(),
+                next_header: None,
+            };
+            (bytes.get(header_length..)?, headers)
+        }
+        L3Packet::Ipv6(ip, extensions, _) => {
+            let fragment_index = extensions
+                .extensions
+                .iter()
+                .position(|ext| ext.is_fragment())?;
+            let unfragmentable_length: usize = extensions.extensions[..fragment_index]
+                .iter()
+                .map(|ext| ext.get_length())
+                .sum();
+            let next_header_offset = match fragment_index {
+                0 => IPV6_NEXT_HEADER_OFFSET,
+                index => {
+                    IPV6_HEADER_LENGTH + unfragmentable_length
+                        - extensions.extensions[index - 1].get_length()
+                }
+            };
+            let fragment_next_header = extensions.extensions[fragment_index]
+                .packet
+                .get_next_header();
+
+            let bytes = ip.packet();
+            let data_start =
+                IPV6_HEADER_LENGTH + unfragmentable_length + IPV6_FRAGMENT_HEADER_LENGTH;
+            let mut headers = l2_header.to_vec();
+            headers.extend_from_slice(bytes.get(..IPV6_HEADER_LENGTH + unfragmentable_length)?);
+            let headers = FirstHeaders {
+                bytes: headers,
+                l3_offset: l2_header.len(),
+                next_header: Some((l2_header.len() + next_header_offset, fragment_next_header)),
+            };
+            (bytes.get(data_start..)?, headers)
+        }
+        // fragments inside MPLS and PPPoE aren't reassembled
+        L3Packet::Arp(_)
+        | L3Packet::Mpls(_, _)
+        | L3Packet::Pppoe(_, _)
+        | L3Packet::Unknown(_, _) => None?,
+    })
+}
+
+/// Builds the reassembled packet from the headers of the first fragment and the data of all the
+/// fragments, and fixes the IP header so it describes a whole packet
+fn reassemble(datagram: &mut Datagram, policy: OverlapPolicy) -> Vec<u8> {
+    let payload = datagram.assemble_payload(policy);
+    let headers = datagram
+        .headers
+        .take()
+        .expect("complete datagram has headers");
+    let l3_offset = headers.l3_offset;
+    let mut packet = headers.bytes;
+    let l3_headers_length = packet.len() - l3_offset;
+    packet.extend_from_slice(&payload);
+
+    match headers.next_header {
+        None => {
+            let mut ip = MutableIpv4Packet::new(&mut packet[l3_offset..])
+                .expect("ipv4 header was validated when parsing");
+            ip.set_total_length((l3_headers_length + payload.len()) as u16);
+            ip.set_flags(ip.get_flags() & !ipv4::Ipv4Flags::MoreFragments);
+            ip.set_fragment_offset(0);
+            let checksum = ipv4::checksum(&ip.to_immutable());
+            ip.set_checksum(checksum);
+        }
+        Some((next_header_offset, next_header)) => {
+            let payload_length = l3_headers_length - IPV6_HEADER_LENGTH + payload.len();
+            packet[l3_offset + 4..l3_offset + 6]
+                .copy_from_slice(&(payload_length as u16).to_be_bytes());
+            packet[next_header_offset] = next_header.0;
+        }
+    }
+    packet
+}
@@ -0,0 +1,184 @@
+use crate::{
+    l4::L4Packet,
+    packet::{HeaderPosition, Packet},
+    tuples::{Direction, FiveTuple},
+};
+use std::{collections::HashMap, time::Duration};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowTableConfig {
+    /// Header position the five tuple is taken from
+    pub position: HeaderPosition,
+    /// Time since the last packet after which a flow expires
+    pub idle_timeout: Duration,
+    /// Time since the first packet after which a flow expires, even if it is still active
+    pub active_timeout: Duration,
+}
+
+impl Default for FlowTableConfig {
+    fn default() -> Self {
+        Self {
+            position: HeaderPosition::Innermost,
+            idle_timeout: Duration::from_secs(15),
+            active_timeout: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+/// Counters of one direction of a flow
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FlowCounters {
+    pub packets: u64,
+    /// Bytes of the L3 packets the tuple was taken from
+    pub bytes: u64,
+    /// Union of all the TCP flags seen
+    pub tcp_flags: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flow {
+    /// Tuple of the first packet seen in the flow, `Direction::Forward` is relative to it
+    pub tuple: FiveTuple,
+    /// Position the tuple came from (`Outer`, `Inner` or `Nth` from the third level on,
+    /// even if `Innermost` was configured)
+    pub position: HeaderPosition,
+    pub forward: FlowCounters,
+    pub reverse: FlowCounters,
+    pub first_seen: Duration,
+    pub last_seen: Duration,
+}
+
+impl Flow {
+    pub fn get_counters(&self, direction: Direction) -> &FlowCounters {
+        match direction {
+            Direction::Forward => &self.forward,
+            Direction::Reverse => &self.reverse,
+        }
+    }
+
+    fn get_counters_mut(&mut self, direction: Direction) -> &mut FlowCounters {
+        match direction {
+            Direction::Forward => &mut self.forward,
+            Direction::Reverse => &mut self.reverse,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpiryReason {
+    /// No packets were seen for the idle timeout
+    Idle,
+    /// The flow lasted longer than the active timeout
+    Active,
+}
+
+/// Table of bidirectional flows, keyed by the canonical form of their five tuple.
+///
+/// Time is taken from the caller (the capture timestamps), so captures can be replayed.
+#[derive(Debug, Default)]
+pub struct FlowTable {
+    config: FlowTableConfig,
+    flows: HashMap<FiveTuple, Flow>,
+}
+
+impl FlowTable {
+    pub fn new(config: FlowTableConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Accounts a packet to its flow, and returns the flow and the direction of the packet in it.
+    /// Packets without a five tuple aren't accounted
+    pub fn process(&mut self, packet: &Packet, timestamp: Duration) -> Option<(&Flow, Direction)> {
+        let position = match self.config.position {
+            HeaderPosition::Innermost => HeaderPosition::Nth(
+                (0..packet.get_levels().len())
+                    .rev()
+                    .find(|index| packet.get_five_tuple(HeaderPosition::Nth(*index)).is_some())?,
+            ),
+            position => position,
+        };
+        // the same level is always recorded with the same position
+        let position = match position {
+            HeaderPosition::Nth(0) => HeaderPosition::Outer,
+            HeaderPosition::Nth(1) => HeaderPosition::Inner,
+            position => position,
+        };
+        let tuple = packet.get_five_tuple(position)?;
+        let l3 = packet.get_l3(position)?;
+        let tcp_flags = match l3.get_l4() {
+            Some(L4Packet::Tcp(tcp)) => tcp.get_flags(),
+            _ => 0,
+        };
+
+        let (key, _) = tuple.canonical();
+        let flow = self.flows.entry(key).or_insert_with(|| Flow {
+            tuple: tuple.clone(),
+            position,
+            forward: Default::default(),
+            reverse: Default::default(),
+            first_seen: timestamp,
+            last_seen: timestamp,
+        });
+        let direction = match flow.tuple == tuple {
+            true => Direction::Forward,
+            false => Direction::Reverse,
+        };
+        flow.last_seen = flow.last_seen.max(timestamp);
+        let counters = flow.get_counters_mut(direction);
+        counters.packets += 1;
+        counters.bytes += l3.get_length() as u64;
+        counters.tcp_flags |= tcp_flags;
+
+        Some((flow, direction))
+    }
+
+    /// Gets the flow of a tuple, in any of its directions
+    pub fn get(&self, tuple: &FiveTuple) -> Option<&Flow> {
+        self.flows.get(&tuple.canonical().0)
+    }
+
+    /// Removes the flows that reached a timeout, and iterates over them
+    pub fn expire(&mut self, now: Duration) -> impl Iterator<Item = (Flow, ExpiryReason)> {
+        let expired: Vec<_> = self
+            .flows
+            .iter()
+            .filter_map(|(key, flow)| Some((key.clone(), self.expiry_reason(flow, now)?)))
+            .collect();
+        let flows = &mut self.flows;
+        expired
+            .into_iter()
+            .filter_map(move |(key, reason)| Some((flows.remove(&key)?, reason)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Removes all the flows, like at the end of a capture
+    pub fn drain(&mut self) -> impl Iterator<Item = Flow> + '_ {
+        self.flows.drain().map(|(_, flow)| flow)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Flow> {
+        self.flows.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    fn expiry_reason(&self, flow: &Flow, now: Duration) -> Option<ExpiryReason> {
+        if now.saturating_sub(flow.last_seen) >= self.config.idle_timeout {
+            Some(ExpiryReason::Idle)
+        } else if now.saturating_sub(flow.first_seen) >= self.config.active_timeout {
+            Some(ExpiryReason::Active)
+        } else {
+            None
+        }
+    }
+}
@@ -1,7 +1,17 @@
-use crate::l3::{self, L3Packet};
+use crate::{
+    l3::{self, L3Packet},
+    parse_options::ParseOptions,
+    protocols::{
+        llc::LlcPacket,
+        loopback::{LoopbackPacket, LOOPBACK_HEADER_LENGTH},
+    },
+};
 use pnet::packet::{
-    ethernet::{EtherTypes, EthernetPacket},
+    ethernet::{EtherType, EtherTypes, EthernetPacket},
+    sll::SLLPacket,
+    sll2::SLL2Packet,
     vlan::VlanPacket,
+    Packet as _,
 };
 use smallvec::SmallVec;
 use std::fmt::Display;
@@ -13,29 +23,77 @@ pub enum ParseError {
     Ethernet,
     #[error("Failed to parse Vlan")]
     Vlan,
+    #[error("Failed to parse LLC")]
+    Llc,
+    #[error("Too many Vlan tags")]
+    TooManyVlans,
+    #[error("Failed to parse Linux cooked capture header")]
+    LinuxSll,
+    #[error("Failed to parse loopback header")]
+    Loopback,
     #[error("Error in L3")]
     L3Error(#[from] l3::ParseError),
 }
 
 type VlanPackets<'a> = SmallVec<[VlanPacket<'a>; 2]>;
 
+const ETHERNET_LENGTH_WITHOUT_PROTOCOL: usize = 14;
+const VLAN_LENGTH: usize = 4;
+/// Values of the ethertype field below this are an 802.3 length
+const IEEE_802_3_MAX_LENGTH: u16 = 0x0600;
+const SLL_LENGTH: usize = 16;
+const SLL2_LENGTH: usize = 20;
+/// Maximal length of the address in the Linux cooked capture headers
+const SLL_ADDRESS_LENGTH: usize = 8;
+
+/// Transparent ethernet bridging, the protocol of tunnels that carry ethernet frames
+pub const TRANSPARENT_ETHERNET_BRIDGING: EtherType = EtherType(0x6558);
+
+/// Legacy S-tag TPID used by some vendors before 802.1ad was standardized
+const QINQ_LEGACY_TPID: EtherType = EtherType(0x9200);
+
+/// Checks if an ethertype is a TPID of a vlan tag (C-tag or S-tag)
+pub fn is_vlan_tpid(ethertype: EtherType) -> bool {
+    matches!(
+        ethertype,
+        EtherTypes::Vlan | EtherTypes::PBridge | EtherTypes::QinQ | QINQ_LEGACY_TPID
+    )
+}
+
 #[derive(Debug, PartialEq)]
 pub enum L2Packet<'a> {
     Ethernet(EthernetPacket<'a>, VlanPackets<'a>, L3Packet<'a>),
+    /// IEEE 802.3 frame (length instead of ethertype) with an LLC header.
+    /// Only SNAP frames of ethernet protocols have an L3 packet
+    Llc(
+        EthernetPacket<'a>,
+        VlanPackets<'a>,
+        LlcPacket<'a>,
+        Option<L3Packet<'a>>,
+    ),
+    /// Linux cooked capture (`tcpdump -i any`)
+    LinuxSll(SLLPacket<'a>, L3Packet<'a>),
+    /// Linux cooked capture v2, which also has the interface index
+    LinuxSll2(SLL2Packet<'a>, L3Packet<'a>),
+    /// BSD loopback, only IPv4 and IPv6 families have an L3 packet
+    Loopback(LoopbackPacket<'a>, Option<L3Packet<'a>>),
 }
 
 impl<'a> TryFrom<&'a [u8]> for L2Packet<'a> {
     type Error = ParseError;
 
-    fn try_from(mut bytes: &'a [u8]) -> Result<Self, Self::Error> {
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        Self::parse_with(bytes, &ParseOptions::default())
+    }
+}
+
+impl<'a> L2Packet<'a> {
+    /// Parse an L2 packet, while enforcing the limits in `options`
+    pub fn parse_with(mut bytes: &'a [u8], options: &ParseOptions) -> Result<Self, ParseError> {
         // this code is complex due to supporting any amount of vlans
         // (will not allocate memory unless more than 2 vlans)
-        // may be vunrable, because technically it could receive a lot
-        // of fucked up packets (100 vlans) and slow down
-        // todo: consider adding a limit and maybe offer another function without one
-        const ETHERNET_LENGTH_WITHOUT_PROTOCOL: usize = 14;
-        const VLAN_LENGTH: usize = 4;
-
+        // a lot of stacked vlans can slow the parsing down, so their amount
+        // can be limited with `ParseOptions::max_vlan_tags`
         let header = EthernetPacket::new(bytes).ok_or(ParseError::Ethernet)?;
         bytes = bytes
             .get(ETHERNET_LENGTH_WITHOUT_PROTOCOL..)
@@ -43,34 +101,167 @@ impl<'a> TryFrom<&'a [u8]> for L2Packet<'a> {
 
         let mut vlans = VlanPackets::new();
         let mut ethertype = header.get_ethertype();
-        while ethertype == EtherTypes::Vlan {
+        while is_vlan_tpid(ethertype) {
+            if options.max_vlan_tags == Some(vlans.len()) {
+                return Err(ParseError::TooManyVlans);
+            }
             let vlan_packet = VlanPacket::new(bytes).ok_or(ParseError::Vlan)?;
             bytes = bytes.get(VLAN_LENGTH..).ok_or(ParseError::Vlan)?;
             ethertype = vlan_packet.get_ethertype();
             vlans.push(vlan_packet);
         }
-        let l3 = (ethertype, bytes).try_into()?;
+        if ethertype.0 < IEEE_802_3_MAX_LENGTH {
+            // captures may be cut before the declared length
+            let bytes = bytes.get(..ethertype.0.into()).unwrap_or(bytes);
+            let llc = LlcPacket::new(bytes).ok_or(ParseError::Llc)?;
+            let l3 = match llc.get_ether_type() {
+                Some(ether_type) => {
+                    let payload = &bytes[llc.get_header_length()..];
+                    Some(L3Packet::parse_with(ether_type, payload, options)?)
+                }
+                None => None,
+            };
+            return Ok(Self::Llc(header, vlans, llc, l3));
+        }
+        let l3 = L3Packet::parse_with(ethertype, bytes, options)?;
 
         Ok(Self::Ethernet(header, vlans, l3))
     }
-}
 
-impl<'a> L2Packet<'a> {
+    /// Parse a Linux cooked capture (`LINKTYPE_LINUX_SLL`) packet
+    pub fn parse_sll_with(bytes: &'a [u8], options: &ParseOptions) -> Result<Self, ParseError> {
+        // pnet doesn't count the address in the minimal length, so the header length is checked here
+        let payload = bytes.get(SLL_LENGTH..).ok_or(ParseError::LinuxSll)?;
+        let header = SLLPacket::new(bytes).ok_or(ParseError::LinuxSll)?;
+        let l3 = L3Packet::parse_with(header.get_protocol(), payload, options)?;
+        Ok(Self::LinuxSll(header, l3))
+    }
+
+    /// Parse a Linux cooked capture v2 (`LINKTYPE_LINUX_SLL2`) packet
+    pub fn parse_sll2_with(bytes: &'a [u8], options: &ParseOptions) -> Result<Self, ParseError> {
+        let payload = bytes.get(SLL2_LENGTH..).ok_or(ParseError::LinuxSll)?;
+        let header = SLL2Packet::new(bytes).ok_or(ParseError::LinuxSll)?;
+        let l3 = L3Packet::parse_with(header.get_protocol_type(), payload, options)?;
+        Ok(Self::LinuxSll2(header, l3))
+    }
+
+    /// Parse a BSD loopback (`LINKTYPE_NULL`) packet, with a host byte order family
+    pub fn parse_null_with(bytes: &'a [u8], options: &ParseOptions) -> Result<Self, ParseError> {
+        let header = LoopbackPacket::new_null(bytes).ok_or(ParseError::Loopback)?;
+        Self::parse_loopback_with(header, &bytes[LOOPBACK_HEADER_LENGTH..], options)
+    }
+
+    /// Parse an OpenBSD loopback (`LINKTYPE_LOOP`) packet, with a network byte order family
+    pub fn parse_loop_with(bytes: &'a [u8], options: &ParseOptions) -> Result<Self, ParseError> {
+        let header = LoopbackPacket::new_loop(bytes).ok_or(ParseError::Loopback)?;
+        Self::parse_loopback_with(header, &bytes[LOOPBACK_HEADER_LENGTH..], options)
+    }
+
+    fn parse_loopback_with(
+        header: LoopbackPacket<'a>,
+        payload: &'a [u8],
+        options: &ParseOptions,
+    ) -> Result<Self, ParseError> {
+        let l3 = match header.get_ether_type() {
+            Some(ether_type) => Some(L3Packet::parse_with(ether_type, payload, options)?),
+            None => None,
+        };
+        Ok(Self::Loopback(header, l3))
+    }
+
     pub fn get_l3(&self) -> Option<&L3Packet<'a>> {
         match self {
-            L2Packet::Ethernet(_, _, l3) => Some(l3),
+            L2Packet::Ethernet(_, _, l3)
+            | L2Packet::LinuxSll(_, l3)
+            | L2Packet::LinuxSll2(_, l3) => Some(l3),
+            L2Packet::Llc(_, _, _, l3) | L2Packet::Loopback(_, l3) => l3.as_ref(),
+        }
+    }
+
+    pub fn get_vlans(&self) -> &[VlanPacket<'a>] {
+        match self {
+            L2Packet::Ethernet(_, vlans, _) | L2Packet::Llc(_, vlans, _, _) => vlans,
+            L2Packet::LinuxSll(..) | L2Packet::LinuxSll2(..) | L2Packet::Loopback(..) => &[],
         }
     }
 
-    pub fn get_vlans(&self) -> &'a VlanPackets {
+    pub fn get_vlan_at(&self, index: usize) -> Option<&VlanPacket<'a>> {
+        self.get_vlans().get(index)
+    }
+
+    /// Link layer address of the sender (the source MAC for ethernet).
+    /// Linux cooked captures only have it for some device types
+    pub fn get_source_address(&self) -> &[u8] {
+        let (address, length) = match self {
+            L2Packet::Ethernet(header, _, _) | L2Packet::Llc(header, _, _, _) => {
+                return &header.packet()[6..12]
+            }
+            L2Packet::Loopback(..) => return &[],
+            L2Packet::LinuxSll(header, _) => (
+                &header.packet()[6..14],
+                header.get_link_layer_address_len() as usize,
+            ),
+            L2Packet::LinuxSll2(header, _) => (
+                &header.packet()[12..20],
+                header.get_link_layer_address_length() as usize,
+            ),
+        };
+        &address[..length.min(SLL_ADDRESS_LENGTH)]
+    }
+
+    /// All the bytes of the frame, from the L2 header to the trailer
+    pub fn get_bytes(&self) -> &[u8] {
         match self {
-            L2Packet::Ethernet(_, vlans, _) => vlans,
+            L2Packet::Ethernet(header, _, _) | L2Packet::Llc(header, _, _, _) => header.packet(),
+            L2Packet::LinuxSll(header, _) => header.packet(),
+            L2Packet::LinuxSll2(header, _) => header.packet(),
+            L2Packet::Loopback(header, _) => header.packet(),
         }
     }
 
-    pub fn get_vlan_at(&self, index: usize) -> Option<&VlanPacket> {
+    /// Bytes of the L2 header (including vlan tags), before the L3 packet
+    pub fn get_header(&self) -> &[u8] {
+        let length = match self {
+            L2Packet::Ethernet(_, vlans, _) => {
+                ETHERNET_LENGTH_WITHOUT_PROTOCOL + VLAN_LENGTH * vlans.len()
+            }
+            L2Packet::Llc(_, vlans, llc, _) => {
+                ETHERNET_LENGTH_WITHOUT_PROTOCOL
+                    + VLAN_LENGTH * vlans.len()
+                    + llc.get_header_length()
+            }
+            L2Packet::LinuxSll(..) => SLL_LENGTH,
+            L2Packet::LinuxSll2(..) => SLL2_LENGTH,
+            L2Packet::Loopback(..) => LOOPBACK_HEADER_LENGTH,
+        };
+        &self.get_bytes()[..length]
+    }
+
+    /// Bytes after the L3 packet (ethernet padding, FCS, etc)
+    pub fn get_trailer(&self) -> &[u8] {
+        let l3_end = match self {
+            // the 802.3 length bounds the payload, even if it isn't parsed
+            L2Packet::Llc(_, vlans, llc, _) => {
+                ETHERNET_LENGTH_WITHOUT_PROTOCOL + VLAN_LENGTH * vlans.len() + llc.packet().len()
+            }
+            _ => self.get_header().len() + self.get_l3().map_or(0, L3Packet::get_length),
+        };
+        self.get_bytes().get(l3_end..).unwrap_or_default()
+    }
+
+    /// The TPID of a vlan tag is the ethertype of the header before it,
+    /// so it is taken from the ethernet header or the previous tag.
+    /// Useful to tell S-tags (0x88a8, 0x9100, 0x9200) from C-tags (0x8100)
+    pub fn get_vlan_tpid_at(&self, index: usize) -> Option<EtherType> {
         match self {
-            L2Packet::Ethernet(_, vlan, _) => vlan.get(index),
+            L2Packet::Ethernet(header, vlans, _) | L2Packet::Llc(header, vlans, _, _) => {
+                vlans.get(index)?;
+                Some(match index {
+                    0 => header.get_ethertype(),
+                    index => vlans[index - 1].get_ethertype(),
+                })
+            }
+            L2Packet::LinuxSll(..) | L2Packet::LinuxSll2(..) | L2Packet::Loopback(..) => None,
         }
     }
 }
@@ -81,6 +272,26 @@ impl<'a> Display for L2Packet<'a> {
             L2Packet::Ethernet(_, vlans, l3) => {
                 write!(f, "Ethernet, {} Vlans, {}", vlans.len(), l3)
             }
+            L2Packet::Llc(_, vlans, llc, l3) => {
+                write!(f, "802.3, {} Vlans, ", vlans.len())?;
+                match l3 {
+                    Some(l3) => write!(f, "SNAP, {}", l3),
+                    None => write!(f, "LLC ({:#04x})", llc.get_dsap()),
+                }
+            }
+            L2Packet::LinuxSll(_, l3) => write!(f, "Linux SLL, {}", l3),
+            L2Packet::LinuxSll2(header, l3) => {
+                write!(
+                    f,
+                    "Linux SLL2 (ifindex {}), {}",
+                    header.get_interface_index(),
+                    l3
+                )
+            }
+            L2Packet::Loopback(_, Some(l3)) => write!(f, "Loopback, {}", l3),
+            L2Packet::Loopback(header, None) => {
+                write!(f, "Loopback, Unknown family ({})", header.get_family())
+            }
         }
     }
 }
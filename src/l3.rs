@@ -1,124 +1,490 @@
 use crate::{
-    l3_extensions::ipv6_extensions::{self, Ipv6Extensions},
+    l3_extensions::{
+        ipv4_options::Ipv4ZeroCopyOptionsIterator,
+        ipv6_extensions::{self, Ipv6Extensions},
+    },
     l4::{self, L4Packet},
+    parse_options::{MplsPseudowire, ParseOptions},
+    protocols::{
+        mpls::{MplsLabel, MPLS_LABEL_LENGTH},
+        ppp::{self, PppControl, PppPacket},
+        pppoe::{PppoePacket, PPPOE_HEADER_LENGTH},
+    },
 };
 use pnet::packet::{
     arp::ArpPacket,
-    ethernet::{EtherType, EtherTypes},
+    ethernet::{EtherType, EtherTypes, EthernetPacket},
     ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
-    ipv4::Ipv4Packet,
+    ipv4::{Ipv4Flags, Ipv4Packet},
     ipv6::Ipv6Packet,
-    Packet as _, PacketSize,
+    Packet as _,
 };
+use smallvec::SmallVec;
 use std::{fmt::Display, net::IpAddr};
 
+/// Length of the control word of MPLS pseudowires (RFC 4385)
+const MPLS_CONTROL_WORD_LENGTH: usize = 4;
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParseError {
     #[error("Failed to parse IPv4")]
     IPv4,
+    #[error("Too many IPv4 options")]
+    TooManyIpv4Options,
+    #[error("IPv4 total length is larger than the captured bytes")]
+    TruncatedIPv4,
     #[error("Failed to parse IPv6")]
     IPv6,
+    #[error("IPv6 payload length is larger than the captured bytes")]
+    TruncatedIPv6,
     #[error("Failed to parse Arp")]
     Arp,
+    #[error("Failed to parse MPLS")]
+    Mpls,
+    #[error("Too many MPLS labels")]
+    TooManyMplsLabels,
+    #[error("Failed to parse PPPoE")]
+    Pppoe,
+    #[error("Failed to parse PPP")]
+    Ppp,
     #[error("Error in L4")]
     L4Error(#[from] l4::ParseError),
     #[error("Error in IPv6 extentions")]
     Ipv6ExtensionError(#[from] ipv6_extensions::ParseError),
+    #[deprecated(note = "unknown ethertypes are parsed as `L3Packet::Unknown`")]
     #[error("Unknown L3 protocol")]
     UnknownL3Protocol,
 }
 
+pub type MplsLabels<'a> = SmallVec<[MplsLabel<'a>; 2]>;
+
 #[derive(Debug, PartialEq)]
 pub enum L3Packet<'a> {
     Ipv4(Ipv4Packet<'a>, L4Packet<'a>),
     Ipv6(Ipv6Packet<'a>, Ipv6Extensions<'a>, L4Packet<'a>),
     Arp(ArpPacket<'a>),
+    /// MPLS label stack, ending with the bottom of stack label
+    Mpls(MplsLabels<'a>, MplsPayload<'a>),
+    Pppoe(PppoePacket<'a>, PppoePayload<'a>),
+    /// Opaque payload of an ethertype that isn't supported
+    Unknown(EtherType, &'a [u8]),
+}
+
+/// What comes after the bottom label of an MPLS label stack
+#[derive(Debug, PartialEq)]
+pub enum MplsPayload<'a> {
+    Ipv4(Ipv4Packet<'a>, L4Packet<'a>),
+    Ipv6(Ipv6Packet<'a>, Ipv6Extensions<'a>, L4Packet<'a>),
+    /// Ethernet pseudowire, see [`MplsPseudowire::Ethernet`], with its control word (if configured).
+    /// The frame is parsed as the next level of the [`Packet`](crate::packet::Packet)
+    Ethernet(Option<u32>, EthernetPacket<'a>),
+    Unknown(&'a [u8]),
+}
+
+impl MplsPayload<'_> {
+    /// Length of the payload, including the pseudowire control word
+    pub fn get_length(&self) -> usize {
+        match self {
+            MplsPayload::Ipv4(header, _) => header.packet().len(),
+            MplsPayload::Ipv6(header, _, _) => header.packet().len(),
+            MplsPayload::Ethernet(control_word, frame) => {
+                control_word.map_or(0, |_| MPLS_CONTROL_WORD_LENGTH) + frame.packet().len()
+            }
+            MplsPayload::Unknown(payload) => payload.len(),
+        }
+    }
+}
+
+/// What comes after a PPPoE header
+#[derive(Debug, PartialEq)]
+pub enum PppoePayload<'a> {
+    /// Discovery packets carry tags, see [`PppoePacket::get_tags`]
+    Discovery,
+    Ipv4(PppPacket<'a>, Ipv4Packet<'a>, L4Packet<'a>),
+    Ipv6(
+        PppPacket<'a>,
+        Ipv6Packet<'a>,
+        Ipv6Extensions<'a>,
+        L4Packet<'a>,
+    ),
+    /// LCP, NCP and authentication messages
+    Control(PppPacket<'a>, PppControl<'a>),
+    Unknown(PppPacket<'a>),
 }
 
 impl<'a> TryFrom<(EtherType, &'a [u8])> for L3Packet<'a> {
     type Error = ParseError;
 
     fn try_from((ether_type, bytes): (EtherType, &'a [u8])) -> Result<Self, Self::Error> {
+        Self::parse_with(ether_type, bytes, &ParseOptions::default())
+    }
+}
+
+impl<'a> L3Packet<'a> {
+    /// Parse an L3 packet, while enforcing the limits in `options`
+    pub fn parse_with(
+        ether_type: EtherType,
+        bytes: &'a [u8],
+        options: &ParseOptions,
+    ) -> Result<Self, ParseError> {
         Ok(match ether_type {
             EtherTypes::Ipv4 => {
-                let ip = Ipv4Packet::new(bytes).ok_or(ParseError::IPv4)?;
-                let next_protocol = ip.get_next_level_protocol();
-                let l4_start = bytes.len() - ip.payload().len();
-                let l4_packet = (
-                    next_protocol,
-                    bytes.get(l4_start..).ok_or(ParseError::IPv4)?,
-                )
-                    .try_into()?;
-
+                let (ip, l4_packet) = parse_ipv4(bytes, options)?;
                 Self::Ipv4(ip, l4_packet)
             }
             EtherTypes::Ipv6 => {
-                let ip = Ipv6Packet::new(bytes).ok_or(ParseError::IPv6)?;
-                let header_length = ip.packet_size() - ip.payload().len();
-                let extensions: Ipv6Extensions = (
-                    bytes.get(header_length..).ok_or(ParseError::IPv6)?,
-                    ip.get_next_header(),
-                )
-                    .try_into()?;
-                let l4_packet: L4Packet = (
-                    extensions.next_protocol,
-                    bytes
-                        .get(header_length + extensions.length..)
-                        .ok_or(ParseError::IPv6)?,
-                )
-                    .try_into()?;
-
+                let (ip, extensions, l4_packet) = parse_ipv6(bytes, options)?;
                 Self::Ipv6(ip, extensions, l4_packet)
             }
-            EtherTypes::Arp => Self::Arp(ArpPacket::new(bytes).ok_or(ParseError::Arp)?),
-            _ => Err(ParseError::UnknownL3Protocol)?,
+            EtherTypes::Arp => {
+                let bytes = bytes
+                    .get(..ArpPacket::minimum_packet_size())
+                    .ok_or(ParseError::Arp)?;
+                Self::Arp(ArpPacket::new(bytes).ok_or(ParseError::Arp)?)
+            }
+            EtherTypes::Mpls | EtherTypes::MplsMcast => {
+                let (labels, payload) = parse_mpls(bytes, options)?;
+                Self::Mpls(labels, payload)
+            }
+            EtherTypes::PppoeDiscovery => Self::Pppoe(
+                PppoePacket::new(bytes).ok_or(ParseError::Pppoe)?,
+                PppoePayload::Discovery,
+            ),
+            EtherTypes::PppoeSession => {
+                let header = PppoePacket::new(bytes).ok_or(ParseError::Pppoe)?;
+                let payload =
+                    parse_ppp(&bytes[PPPOE_HEADER_LENGTH..header.packet().len()], options)?;
+                Self::Pppoe(header, payload)
+            }
+            ether_type => Self::Unknown(ether_type, bytes),
         })
     }
-}
 
-impl<'a> L3Packet<'a> {
-    pub fn get_source(&self) -> Option<IpAddr> {
+    /// The IP header of the packet, which is either the L3 header itself
+    /// or the one after an MPLS label stack
+    fn get_ip(&self) -> Option<IpRef<'_, 'a>> {
         match self {
-            L3Packet::Ipv4(header, _) => Some(header.get_source().into()),
-            L3Packet::Ipv6(header, _, _) => Some(header.get_source().into()),
-            L3Packet::Arp(_) => None,
+            L3Packet::Ipv4(header, l4)
+            | L3Packet::Mpls(_, MplsPayload::Ipv4(header, l4))
+            | L3Packet::Pppoe(_, PppoePayload::Ipv4(_, header, l4)) => Some(IpRef::V4(header, l4)),
+            L3Packet::Ipv6(header, extensions, l4)
+            | L3Packet::Mpls(_, MplsPayload::Ipv6(header, extensions, l4))
+            | L3Packet::Pppoe(_, PppoePayload::Ipv6(_, header, extensions, l4)) => {
+                Some(IpRef::V6(header, extensions, l4))
+            }
+            L3Packet::Arp(_)
+            | L3Packet::Mpls(_, _)
+            | L3Packet::Pppoe(_, _)
+            | L3Packet::Unknown(_, _) => None,
         }
     }
 
+    pub fn get_source(&self) -> Option<IpAddr> {
+        Some(match self.get_ip()? {
+            IpRef::V4(header, _) => header.get_source().into(),
+            IpRef::V6(header, _, _) => header.get_source().into(),
+        })
+    }
+
     pub fn get_destination(&self) -> Option<IpAddr> {
+        Some(match self.get_ip()? {
+            IpRef::V4(header, _) => header.get_destination().into(),
+            IpRef::V6(header, _, _) => header.get_destination().into(),
+        })
+    }
+
+    /// Checks if this packet is a part of a fragmented IP packet
+    pub fn is_fragment(&self) -> bool {
+        self.fragment_offset().is_some_and(|offset| offset != 0) || self.more_fragments()
+    }
+
+    /// Offset of the fragment data in the original IP payload, in bytes
+    /// (0 for packets that aren't fragmented)
+    pub fn fragment_offset(&self) -> Option<u16> {
+        Some(match self.get_ip()? {
+            IpRef::V4(header, _) => ipv4_fragment_offset(header),
+            IpRef::V6(_, extensions, _) => extensions
+                .get_fragment()
+                .map_or(0, |fragment| fragment.offset),
+        })
+    }
+
+    pub fn more_fragments(&self) -> bool {
+        match self.get_ip() {
+            Some(IpRef::V4(header, _)) => ipv4_more_fragments(header),
+            Some(IpRef::V6(_, extensions, _)) => extensions
+                .get_fragment()
+                .is_some_and(|fragment| fragment.more_fragments),
+            None => false,
+        }
+    }
+
+    /// Identification shared by all the fragments of an IP packet
+    /// (IPv6 packets only have one if they have a fragment header)
+    pub fn fragment_identification(&self) -> Option<u32> {
+        match self.get_ip()? {
+            IpRef::V4(header, _) => Some(header.get_identification().into()),
+            IpRef::V6(_, extensions, _) => Some(extensions.get_fragment()?.identification),
+        }
+    }
+
+    /// Bytes of the L3 packet (headers and payload), without the L2 trailer
+    pub fn get_bytes(&self) -> &[u8] {
         match self {
-            L3Packet::Ipv4(header, _) => Some(header.get_destination().into()),
-            L3Packet::Ipv6(header, _, _) => Some(header.get_destination().into()),
-            L3Packet::Arp(_) => None,
+            L3Packet::Ipv4(header, _) => header.packet(),
+            L3Packet::Ipv6(header, _, _) => header.packet(),
+            L3Packet::Arp(header) => header.packet(),
+            L3Packet::Mpls(labels, payload) => {
+                // every label view continues to the end of the buffer, which may have an L2 trailer
+                let length = labels.len() * MPLS_LABEL_LENGTH + payload.get_length();
+                &labels[0].packet()[..length]
+            }
+            L3Packet::Pppoe(header, _) => header.packet(),
+            L3Packet::Unknown(_, payload) => payload,
         }
     }
 
+    /// Length of the L3 packet (headers and payload), without the L2 trailer
+    pub fn get_length(&self) -> usize {
+        self.get_bytes().len()
+    }
+
     pub fn get_l4(&self) -> Option<&L4Packet<'a>> {
-        match self {
-            L3Packet::Ipv4(_, l4) => Some(l4),
-            L3Packet::Ipv6(_, _, l4) => Some(l4),
-            L3Packet::Arp(_) => None,
+        match self.get_ip()? {
+            IpRef::V4(_, l4) | IpRef::V6(_, _, l4) => Some(l4),
         }
     }
 
     pub fn get_l4_protocol(&self) -> Option<IpNextHeaderProtocol> {
         let l4 = self.get_l4()?;
         Some(match l4 {
+            L4Packet::Fragment(_) => match self.get_ip()? {
+                IpRef::V4(header, _) => header.get_next_level_protocol(),
+                IpRef::V6(_, extensions, _) => extensions.next_protocol,
+            },
             L4Packet::Tcp(_) => IpNextHeaderProtocols::Tcp,
             L4Packet::Udp(_) => IpNextHeaderProtocols::Udp,
             L4Packet::Gre(_) => IpNextHeaderProtocols::Gre,
             L4Packet::Icmp(_) => IpNextHeaderProtocols::Icmp,
             L4Packet::Icmpv6(_) => IpNextHeaderProtocols::Icmpv6,
+            L4Packet::Unknown(protocol, _) => *protocol,
         })
     }
 }
 
+/// IP header of an [`L3Packet`], with its L4
+enum IpRef<'b, 'a> {
+    V4(&'b Ipv4Packet<'a>, &'b L4Packet<'a>),
+    V6(&'b Ipv6Packet<'a>, &'b Ipv6Extensions<'a>, &'b L4Packet<'a>),
+}
+
+fn parse_ipv4<'a>(
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> Result<(Ipv4Packet<'a>, L4Packet<'a>), ParseError> {
+    let ip = Ipv4Packet::new(bytes).ok_or(ParseError::IPv4)?;
+    let bytes = trim_to_ip_length(bytes, ip.get_total_length().into(), options)
+        .ok_or(ParseError::TruncatedIPv4)?;
+    let ip = Ipv4Packet::new(bytes).ok_or(ParseError::IPv4)?;
+    if ParseOptions::exceeds(ip.get_options_zero_copy(), options.max_options) {
+        return Err(ParseError::TooManyIpv4Options);
+    }
+    let next_protocol = ip.get_next_level_protocol();
+    // not taken from the payload, which is empty when the total length is 0
+    let l4_start = ip.get_header_length() as usize * 4;
+    let l4_packet = parse_l4(
+        next_protocol,
+        bytes.get(l4_start..).ok_or(ParseError::IPv4)?,
+        ipv4_fragment_offset(&ip),
+        ipv4_more_fragments(&ip),
+        options,
+    )?;
+    Ok((ip, l4_packet))
+}
+
+fn parse_ipv6<'a>(
+    bytes: &'a [u8],
+    options: &ParseOptions,
+) -> Result<(Ipv6Packet<'a>, Ipv6Extensions<'a>, L4Packet<'a>), ParseError> {
+    const IPV6_HEADER_LENGTH: usize = 40;
+
+    let ip = Ipv6Packet::new(bytes).ok_or(ParseError::IPv6)?;
+    let length = match ip.get_payload_length() {
+        0 => 0,
+        payload_length => IPV6_HEADER_LENGTH + payload_length as usize,
+    };
+    let bytes = trim_to_ip_length(bytes, length, options).ok_or(ParseError::TruncatedIPv6)?;
+    let ip = Ipv6Packet::new(bytes).ok_or(ParseError::IPv6)?;
+    let header_length = IPV6_HEADER_LENGTH;
+    let extensions = Ipv6Extensions::parse_with(
+        bytes.get(header_length..).ok_or(ParseError::IPv6)?,
+        ip.get_next_header(),
+        options,
+    )?;
+    let fragment = extensions.get_fragment();
+    let l4_packet = parse_l4(
+        extensions.next_protocol,
+        bytes
+            .get(header_length + extensions.length..)
+            .ok_or(ParseError::IPv6)?,
+        fragment.map_or(0, |fragment| fragment.offset),
+        fragment.is_some_and(|fragment| fragment.more_fragments),
+        options,
+    )?;
+    Ok((ip, extensions, l4_packet))
+}
+
+fn parse_mpls<'a>(
+    mut bytes: &'a [u8],
+    options: &ParseOptions,
+) -> Result<(MplsLabels<'a>, MplsPayload<'a>), ParseError> {
+    // like vlans, labels are only allocated when there are more than 2 of them
+    let mut labels = MplsLabels::new();
+    loop {
+        if options.max_mpls_labels == Some(labels.len()) {
+            return Err(ParseError::TooManyMplsLabels);
+        }
+        let label = MplsLabel::new(bytes).ok_or(ParseError::Mpls)?;
+        bytes = &bytes[MPLS_LABEL_LENGTH..];
+        let bottom_of_stack = label.get_bottom_of_stack();
+        labels.push(label);
+        if bottom_of_stack {
+            break;
+        }
+    }
+
+    // there is no protocol field in MPLS, the first nibble of IP headers is their version,
+    // and pseudowire control words start with a 0 nibble so they won't be mistaken for IP
+    let payload = match (bytes.first().map(|byte| byte >> 4), options.mpls_pseudowire) {
+        (Some(4), _) => {
+            let (ip, l4_packet) = parse_ipv4(bytes, options)?;
+            MplsPayload::Ipv4(ip, l4_packet)
+        }
+        (Some(6), _) => {
+            let (ip, extensions, l4_packet) = parse_ipv6(bytes, options)?;
+            MplsPayload::Ipv6(ip, extensions, l4_packet)
+        }
+        (_, MplsPseudowire::Ethernet { control_word }) => {
+            let control_word = match control_word {
+                true => {
+                    let word = bytes
+                        .get(..MPLS_CONTROL_WORD_LENGTH)
+                        .ok_or(ParseError::Mpls)?;
+                    bytes = &bytes[MPLS_CONTROL_WORD_LENGTH..];
+                    Some(u32::from_be_bytes(word.try_into().unwrap()))
+                }
+                false => None,
+            };
+            let frame = EthernetPacket::new(bytes).ok_or(ParseError::Mpls)?;
+            MplsPayload::Ethernet(control_word, frame)
+        }
+        (_, MplsPseudowire::None) => MplsPayload::Unknown(bytes),
+    };
+    Ok((labels, payload))
+}
+
+/// The PPP payload of a PPPoE session packet, `bytes` are bounded by the PPPoE length
+fn parse_ppp<'a>(bytes: &'a [u8], options: &ParseOptions) -> Result<PppoePayload<'a>, ParseError> {
+    let ppp = PppPacket::new(bytes).ok_or(ParseError::Ppp)?;
+    let payload = &bytes[bytes.len() - ppp.payload().len()..];
+    Ok(match ppp.get_protocol() {
+        ppp::protocols::IPV4 => {
+            let (ip, l4_packet) = parse_ipv4(payload, options)?;
+            PppoePayload::Ipv4(ppp, ip, l4_packet)
+        }
+        ppp::protocols::IPV6 => {
+            let (ip, extensions, l4_packet) = parse_ipv6(payload, options)?;
+            PppoePayload::Ipv6(ppp, ip, extensions, l4_packet)
+        }
+        protocol if PppControl::is_control_protocol(protocol) => {
+            let control = PppControl::new(protocol, payload).ok_or(ParseError::Ppp)?;
+            PppoePayload::Control(ppp, control)
+        }
+        _ => PppoePayload::Unknown(ppp),
+    })
+}
+
+fn ipv4_fragment_offset(header: &Ipv4Packet) -> u16 {
+    header.get_fragment_offset() * 8
+}
+
+fn ipv4_more_fragments(header: &Ipv4Packet) -> bool {
+    header.get_flags() & Ipv4Flags::MoreFragments != 0
+}
+
+/// Non-first fragments don't start with an L4 header, so their payload is kept as a fragment.
+/// The same goes for a first fragment that is too short to contain the whole L4 header
+fn parse_l4<'a>(
+    next_protocol: IpNextHeaderProtocol,
+    bytes: &'a [u8],
+    fragment_offset: u16,
+    more_fragments: bool,
+    options: &ParseOptions,
+) -> Result<L4Packet<'a>, l4::ParseError> {
+    if fragment_offset != 0 {
+        return Ok(L4Packet::Fragment(bytes));
+    }
+    match L4Packet::parse_with(next_protocol, bytes, options) {
+        Err(_) if more_fragments => Ok(L4Packet::Fragment(bytes)),
+        result => result,
+    }
+}
+
+/// Bounds an IP packet to the length declared in its header, so ethernet padding
+/// won't be parsed as part of the L4. A length of 0 is used by TSO (and jumbograms),
+/// and means that the whole buffer is used.
+fn trim_to_ip_length<'a>(
+    bytes: &'a [u8],
+    length: usize,
+    options: &ParseOptions,
+) -> Option<&'a [u8]> {
+    match length {
+        0 => Some(bytes),
+        length if length > bytes.len() => (!options.strict_ip_length).then_some(bytes),
+        length => bytes.get(..length),
+    }
+}
+
 impl Display for L3Packet<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             L3Packet::Ipv4(_, l4) => write!(f, "IPv4, {}", l4),
             L3Packet::Ipv6(_, _, l4) => write!(f, "IPv6, {}", l4),
             L3Packet::Arp(_) => write!(f, "Arp"),
+            L3Packet::Mpls(labels, payload) => {
+                write!(f, "MPLS ({} labels), {}", labels.len(), payload)
+            }
+            L3Packet::Pppoe(_, payload) => write!(f, "PPPoE, {}", payload),
+            L3Packet::Unknown(ether_type, _) => write!(f, "Unknown ({:#06x})", ether_type.0),
+        }
+    }
+}
+
+impl Display for MplsPayload<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MplsPayload::Ipv4(_, l4) => write!(f, "IPv4, {}", l4),
+            MplsPayload::Ipv6(_, _, l4) => write!(f, "IPv6, {}", l4),
+            MplsPayload::Ethernet(_, _) => write!(f, "Ethernet pseudowire"),
+            MplsPayload::Unknown(_) => write!(f, "Unknown"),
+        }
+    }
+}
+
+impl Display for PppoePayload<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PppoePayload::Discovery => write!(f, "Discovery"),
+            PppoePayload::Ipv4(_, _, l4) => write!(f, "IPv4, {}", l4),
+            PppoePayload::Ipv6(_, _, _, l4) => write!(f, "IPv6, {}", l4),
+            PppoePayload::Control(_, control) => match control {
+                PppControl::Lcp(_) => write!(f, "LCP"),
+                PppControl::Ipcp(_) => write!(f, "IPCP"),
+                PppControl::Ipv6cp(_) => write!(f, "IPV6CP"),
+                PppControl::Pap(_) => write!(f, "PAP"),
+                PppControl::Chap(_) => write!(f, "CHAP"),
+            },
+            PppoePayload::Unknown(ppp) => write!(f, "PPP Unknown ({:#06x})", ppp.get_protocol()),
         }
     }
 }
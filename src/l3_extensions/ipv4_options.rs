@@ -58,11 +58,11 @@ impl<'a> Iterator for Ipv4OptionsIterator<'a> {
 }
 
 pub trait Ipv4ZeroCopyOptionsIterator {
-    fn get_options_zero_copy(&self) -> Ipv4OptionsIterator;
+    fn get_options_zero_copy(&self) -> Ipv4OptionsIterator<'_>;
 }
 
 impl<'a> Ipv4ZeroCopyOptionsIterator for Ipv4Packet<'a> {
-    fn get_options_zero_copy(&self) -> Ipv4OptionsIterator {
+    fn get_options_zero_copy(&self) -> Ipv4OptionsIterator<'_> {
         Ipv4OptionsIterator {
             bytes: self.get_options_raw(),
         }
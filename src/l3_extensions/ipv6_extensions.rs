@@ -3,15 +3,20 @@ use num_traits::FromPrimitive;
 use pnet::packet::{
     ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
     ipv6::ExtensionPacket,
+    Packet as _,
 };
 use smallvec::SmallVec;
 
+use crate::parse_options::ParseOptions;
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParseError {
     #[error("Failed to parse Ipv6 extension")]
     ExtensionParseFailure,
     #[error("Unknown IPv6 extension")]
     UnknownIpv6Extension,
+    #[error("Too many IPv6 extensions")]
+    TooManyExtensions,
 }
 
 #[repr(u8)]
@@ -43,6 +48,42 @@ pub struct Ipv6Extensions<'a> {
     pub length: usize,
 }
 
+/// Fields of an IPv6 fragment extension header
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Ipv6Fragment {
+    /// Offset of the fragment data, in bytes
+    pub offset: u16,
+    pub more_fragments: bool,
+    pub identification: u32,
+}
+
+impl Ipv6Extension<'_> {
+    pub fn is_fragment(&self) -> bool {
+        self.protocol == Ipv6ExtensionProtocolIds::Ipv6Frag
+    }
+
+    /// Length of the extension header in bytes
+    pub fn get_length(&self) -> usize {
+        self.packet.get_hdr_ext_len() as usize * 8 + 8
+    }
+
+    /// Get the fields of the extension, if it's a fragment extension header
+    pub fn get_fragment(&self) -> Option<Ipv6Fragment> {
+        if !self.is_fragment() {
+            return None;
+        }
+        // the fragment header is always 8 bytes:
+        // next header (1), reserved (1), offset + flags (2), identification (4)
+        let bytes = self.packet.packet().get(2..8)?;
+        let offset_and_flags = u16::from_be_bytes([bytes[0], bytes[1]]);
+        Some(Ipv6Fragment {
+            offset: offset_and_flags & 0xfff8,
+            more_fragments: offset_and_flags & 1 != 0,
+            identification: u32::from_be_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]),
+        })
+    }
+}
+
 impl Ipv6Extensions<'_> {
     fn new(next_protocol: IpNextHeaderProtocol) -> Self {
         Self {
@@ -51,6 +92,11 @@ impl Ipv6Extensions<'_> {
             length: Default::default(),
         }
     }
+
+    /// Get the fragment extension header, if the packet is fragmented
+    pub fn get_fragment(&self) -> Option<Ipv6Fragment> {
+        self.extensions.iter().find_map(|ext| ext.get_fragment())
+    }
 }
 
 impl<'a> TryFrom<(IpNextHeaderProtocol, &'a [u8])> for Ipv6Extension<'a> {
@@ -71,23 +117,44 @@ impl<'a> TryFrom<(&'a [u8], IpNextHeaderProtocol)> for Ipv6Extensions<'a> {
     type Error = ParseError;
 
     fn try_from(
-        (mut buf, mut next_protocol): (&'a [u8], IpNextHeaderProtocol),
+        (buf, next_protocol): (&'a [u8], IpNextHeaderProtocol),
     ) -> Result<Self, Self::Error> {
+        Self::parse_with(buf, next_protocol, &ParseOptions::default())
+    }
+}
+
+impl<'a> Ipv6Extensions<'a> {
+    /// Parse the IPv6 extensions chain, while enforcing the limits in `options`
+    pub fn parse_with(
+        mut buf: &'a [u8],
+        mut next_protocol: IpNextHeaderProtocol,
+        options: &ParseOptions,
+    ) -> Result<Self, ParseError> {
         let mut extensions = Ipv6Extensions::new(next_protocol);
         loop {
             let extension: Result<Ipv6Extension, ParseError> = (next_protocol, buf).try_into();
             match extension {
+                Ok(_) if options.max_ipv6_extensions == Some(extensions.extensions.len()) => {
+                    Err(ParseError::TooManyExtensions)?
+                }
                 Ok(extension) => {
-                    let extension_length = extension.packet.get_hdr_ext_len() as usize * 8 + 8;
+                    let extension_length = extension.get_length();
                     buf = buf
                         .get(extension_length..)
                         .ok_or(ParseError::ExtensionParseFailure)?;
                     next_protocol = extension.packet.get_next_header();
+                    let non_first_fragment = extension
+                        .get_fragment()
+                        .is_some_and(|fragment| fragment.offset != 0);
                     extensions.extensions.push(extension);
                     extensions.length += extension_length;
+                    // the headers after the fragment header are only in the first fragment
+                    if non_first_fragment {
+                        break;
+                    }
                 }
-                Err(ParseError::ExtensionParseFailure) => Err(ParseError::ExtensionParseFailure)?,
                 Err(ParseError::UnknownIpv6Extension) => break,
+                Err(error) => Err(error)?,
             }
         }
 
@@ -1,5 +1,4 @@
 use pnet::packet::{
-    gre::GrePacket,
     icmp::IcmpPacket,
     icmpv6::Icmpv6Packet,
     ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
@@ -8,10 +7,17 @@ use pnet::packet::{
 };
 use std::fmt::Display;
 
+use crate::{
+    l4_extensions::tcp_options::TcpZeroCopyOptionsIterator, parse_options::ParseOptions,
+    protocols::gre::GrePacket,
+};
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParseError {
     #[error("Failed to parse Tcp")]
     Tcp,
+    #[error("Too many Tcp options")]
+    TooManyTcpOptions,
     #[error("Failed to parse Udp")]
     Udp,
     #[error("Failed to parse Gre")]
@@ -20,6 +26,7 @@ pub enum ParseError {
     Icmp,
     #[error("Failed to parse Icmpv6")]
     Icmpv6,
+    #[deprecated(note = "unknown ip protocols are parsed as `L4Packet::Unknown`")]
     #[error("Unknown L4 protocol")]
     UnknownL4Protocol,
 }
@@ -31,32 +38,55 @@ pub enum L4Packet<'a> {
     Gre(GrePacket<'a>),
     Icmp(IcmpPacket<'a>),
     Icmpv6(Icmpv6Packet<'a>),
+    /// Opaque payload of an ip protocol that isn't supported
+    Unknown(IpNextHeaderProtocol, &'a [u8]),
+    /// Payload of an IP fragment that doesn't contain the L4 header
+    Fragment(&'a [u8]),
 }
 
 impl<'a> TryFrom<(IpNextHeaderProtocol, &'a [u8])> for L4Packet<'a> {
     type Error = ParseError;
 
-    #[rustfmt::skip]
     fn try_from(
         (next_protocol, bytes): (IpNextHeaderProtocol, &'a [u8]),
     ) -> Result<Self, Self::Error> {
+        Self::parse_with(next_protocol, bytes, &ParseOptions::default())
+    }
+}
+
+impl<'a> L4Packet<'a> {
+    /// Parse an L4 packet, while enforcing the limits in `options`
+    #[rustfmt::skip]
+    pub fn parse_with(
+        next_protocol: IpNextHeaderProtocol,
+        bytes: &'a [u8],
+        options: &ParseOptions,
+    ) -> Result<Self, ParseError> {
         Ok(match next_protocol {
-            IpNextHeaderProtocols::Tcp => Self::Tcp(TcpPacket::new(bytes).ok_or(ParseError::Tcp)?),
+            IpNextHeaderProtocols::Tcp => {
+                let tcp = TcpPacket::new(bytes).ok_or(ParseError::Tcp)?;
+                if ParseOptions::exceeds(tcp.get_options_zero_copy(), options.max_options) {
+                    return Err(ParseError::TooManyTcpOptions);
+                }
+                Self::Tcp(tcp)
+            }
             IpNextHeaderProtocols::Udp => Self::Udp(UdpPacket::new(bytes).ok_or(ParseError::Udp)?),
             IpNextHeaderProtocols::Gre => Self::Gre(GrePacket::new(bytes).ok_or(ParseError::Gre)?),
             IpNextHeaderProtocols::Icmp => Self::Icmp(IcmpPacket::new(bytes).ok_or(ParseError::Icmp)?),
             IpNextHeaderProtocols::Icmpv6 => Self::Icmpv6(Icmpv6Packet::new(bytes).ok_or(ParseError::Icmpv6)?),
-            _ => Err(ParseError::UnknownL4Protocol)?,
+            next_protocol => Self::Unknown(next_protocol, bytes),
         })
     }
-}
 
-impl<'a> L4Packet<'a> {
     pub fn get_source(&self) -> Option<u16> {
         match self {
             L4Packet::Tcp(header) => Some(header.get_source()),
             L4Packet::Udp(header) => Some(header.get_source()),
-            L4Packet::Gre(_) | L4Packet::Icmp(_) | L4Packet::Icmpv6(_) => None,
+            L4Packet::Gre(_)
+            | L4Packet::Icmp(_)
+            | L4Packet::Icmpv6(_)
+            | L4Packet::Unknown(_, _)
+            | L4Packet::Fragment(_) => None,
         }
     }
 
@@ -64,7 +94,11 @@ impl<'a> L4Packet<'a> {
         match self {
             L4Packet::Tcp(header) => Some(header.get_destination()),
             L4Packet::Udp(header) => Some(header.get_destination()),
-            L4Packet::Gre(_) | L4Packet::Icmp(_) | L4Packet::Icmpv6(_) => None,
+            L4Packet::Gre(_)
+            | L4Packet::Icmp(_)
+            | L4Packet::Icmpv6(_)
+            | L4Packet::Unknown(_, _)
+            | L4Packet::Fragment(_) => None,
         }
     }
 }
@@ -77,6 +111,8 @@ impl Display for L4Packet<'_> {
             L4Packet::Gre(_) => write!(f, "Gre"),
             L4Packet::Icmp(_) => write!(f, "Icmp"),
             L4Packet::Icmpv6(_) => write!(f, "Icmpv6"),
+            L4Packet::Unknown(protocol, _) => write!(f, "Unknown ({})", protocol.0),
+            L4Packet::Fragment(_) => write!(f, "Fragment"),
         }
     }
 }
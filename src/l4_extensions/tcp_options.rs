@@ -50,11 +50,11 @@ impl<'a> Iterator for TcpOptionsIterator<'a> {
 }
 
 pub trait TcpZeroCopyOptionsIterator {
-    fn get_options_zero_copy(&self) -> TcpOptionsIterator;
+    fn get_options_zero_copy(&self) -> TcpOptionsIterator<'_>;
 }
 
 impl<'a> TcpZeroCopyOptionsIterator for TcpPacket<'a> {
-    fn get_options_zero_copy(&self) -> TcpOptionsIterator {
+    fn get_options_zero_copy(&self) -> TcpOptionsIterator<'_> {
         TcpOptionsIterator {
             bytes: self.get_options_raw(),
         }
@@ -6,10 +6,12 @@
 //! It puts more emphasys on all parsing being zero-copy, and all tests are checked for zero allocations
 //! <br>
 //! It currently supports the following protocols:
-//! - `Ethernet`
-//! - `IPv4 (+options)`, `IPv6 (+extension)`, `Arp`
+//! - `Ethernet (+802.1Q/802.1ad vlans, 802.3 LLC/SNAP)`, `Linux cooked capture (SLL, SLL2)`, `BSD loopback`, raw IP
+//! - `IPv4 (+options)`, `IPv6 (+extension)`, `Arp`, `MPLS`, `PPPoE (+PPP control protocols)`
 //! - `TCP`, `UDP`, `ICMP`, `ICMPv6`
-//! - `GRE tunnel`
+//! - `GRE` (+ethernet over GRE and `NVGRE`), `VXLAN`, `Geneve`, `ERSPAN` and IP in IP tunnels, in any depth
+//!
+//! Packets can also be read from and written to `pcap` and `pcapng` capture files, see [`capture`].
 //!
 //! # Parsing packets
 //! Simple parsing of a packet from bytes:
@@ -31,22 +33,33 @@
 //! ```
 //! This code will output the following:
 //! ```text
-//! Regular(
-//!     Ethernet(
-//!         EthernetPacket { destination : 78:2b:46:4b:3b:ab, source : b4:8c:9d:5d:81:8b, ethertype : EtherType(2048),  },
-//!         [],
-//!         Ipv4(
-//!             Ipv4Packet { version : 4, header_length : 5, dscp : 0, ecn : 0, total_length : 50, identification : 13867, flags : 2, fragment_offset : 0, ttl : 128, next_level_protocol : IpNextHeaderProtocol(6), checksum : 2196, source : 192.168.29.17, destination : 192.168.29.165, options : [],  },
-//!             Tcp(
-//!                 TcpPacket { source : 60514, destination : 25565, sequence : 3337593823, acknowledgement : 2295234236, data_offset : 5, reserved : 0, flags : 24, window : 513, checksum : 3715, urgent_ptr : 0, options : [],  },
+//! Packet {
+//!     levels: [
+//!         L2(
+//!             None,
+//!             Ethernet(
+//!                 EthernetPacket { destination : 78:2b:46:4b:3b:ab, source : b4:8c:9d:5d:81:8b, ethertype : EtherType(2048),  },
+//!                 [],
+//!                 Ipv4(
+//!                     Ipv4Packet { version : 4, header_length : 5, dscp : 0, ecn : 0, total_length : 50, identification : 13867, flags : 2, fragment_offset : 0, ttl : 128, next_level_protocol : IpNextHeaderProtocol(6), checksum : 2196, source : 192.168.29.17, destination : 192.168.29.165, options : [],  },
+//!                     Tcp(
+//!                         TcpPacket { source : 60514, destination : 25565, sequence : 3337593823, acknowledgement : 2295234236, data_offset : 5, reserved : 0, flags : 24, window : 513, checksum : 3715, urgent_ptr : 0, options : [],  },
+//!                     ),
+//!                 ),
 //!             ),
 //!         ),
-//!     ),
-//! )
+//!     ],
+//! }
 //! ```
 
 pub use pnet::packet::Packet as PacketTrait;
 
+/// Capture files reading and writing
+pub mod capture;
+/// IPv4 and IPv6 fragments reassembly
+pub mod defrag;
+/// Bidirectional flows tracking
+pub mod flow;
 /// Layer 2 protocols
 pub mod l2;
 /// Layer 3 protocols
@@ -62,4 +75,10 @@ pub mod l4;
 pub mod l4_extensions;
 /// General packet structures (tuples, encapsulations, etc)
 pub mod packet;
+/// Parsing limits
+pub mod parse_options;
+/// Zero-copy headers of protocols that aren't supported by pnet
+pub mod protocols;
+/// TCP streams reassembly
+pub mod tcp_reassembly;
 pub mod tuples;
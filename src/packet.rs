@@ -1,9 +1,24 @@
 use crate::{
-    l2::{self, L2Packet},
-    l3::{self, L3Packet},
-    l4::L4Packet,
+    capture::LinkType,
+    l2::{self, L2Packet, TRANSPARENT_ETHERNET_BRIDGING},
+    l3::{self, L3Packet, MplsPayload},
+    l3_extensions::ipv6_extensions,
+    l4::{self, L4Packet},
+    parse_options::ParseOptions,
+    protocols::{
+        erspan::{
+            self, ErspanPacket, ERSPAN_TYPE_II, ERSPAN_TYPE_III, FRAME_TYPE_ETHERNET, FRAME_TYPE_IP,
+        },
+        geneve::GenevePacket,
+        vxlan::{VxlanPacket, VXLAN_HEADER_LENGTH},
+    },
 };
-use pnet::packet::{ethernet::EtherType, Packet as _};
+use pnet::packet::{
+    ethernet::{EtherType, EtherTypes},
+    ip::IpNextHeaderProtocols,
+    Packet as _,
+};
+use smallvec::SmallVec;
 use std::fmt::Display;
 
 #[derive(thiserror::Error, Debug)]
@@ -14,103 +29,444 @@ pub enum ParseError {
     L2Error(#[from] l2::ParseError),
     #[error("Error in L3")]
     L3Error(#[from] l3::ParseError),
+    #[deprecated(note = "packets without an L3 are valid, this error isn't returned anymore")]
     #[error("Missing L3")]
     MissingL3,
+    #[deprecated(note = "packets without an L4 are valid, this error isn't returned anymore")]
     #[error("Missing L4")]
     MissingL4,
-    #[error("GRE Routing not supported in pnet")]
-    GreRoutingNotSupportedInPnet,
+    #[error("Too many Geneve options")]
+    TooManyGeneveOptions,
+    #[error("Failed to parse ERSPAN")]
+    Erspan,
+    #[error("Tunnel depth exceeded")]
+    TunnelDepthExceeded,
+    #[error("Unsupported link type {0:?}")]
+    UnsupportedLinkType(LinkType),
+    #[error("Invalid IP version {0}")]
+    InvalidIpVersion(u8),
 }
 
+/// Encapsulation levels are only allocated when there are more than 3 of them
+pub type Levels<'a> = SmallVec<[Level<'a>; 3]>;
+
+/// A packet, as a stack of encapsulation levels. The first level is the outer one,
+/// and every tunnel in the L4 of a level adds an inner level after it
 #[derive(Debug, PartialEq)]
 #[repr(align(64))]
-pub enum Packet<'a> {
-    Regular(L2Packet<'a>),
-    L3Tunnel(L2Packet<'a>, L3Packet<'a>),
+pub struct Packet<'a> {
+    /// The buffer the packet was parsed from, including what is after the outer L3
+    bytes: &'a [u8],
+    levels: Levels<'a>,
+}
+
+/// Headers of one encapsulation level, with the tunnel header that carried it (if it isn't a
+/// part of the outer L4, like GRE). The L4 is a part of the L3 packet
+#[derive(Debug, PartialEq)]
+pub enum Level<'a> {
+    /// Level that starts with an L2 header
+    L2(Option<Encapsulation<'a>>, L2Packet<'a>),
+    /// Level that starts with an L3 header (raw IP, IP in GRE, IP in IP, etc)
+    L3(Option<Encapsulation<'a>>, L3Packet<'a>),
+}
+
+/// Header of a tunnel that is carried in the L4 payload of the outer level
+#[derive(Debug, PartialEq)]
+pub enum Encapsulation<'a> {
+    Vxlan(VxlanPacket<'a>),
+    Geneve(GenevePacket<'a>),
+    /// ERSPAN type II or III, type I has no header and no encapsulation
+    Erspan(ErspanPacket<'a>),
 }
 
 impl<'a> TryFrom<&'a [u8]> for Packet<'a> {
     type Error = ParseError;
 
     fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
-        let l2 = L2Packet::try_from(bytes)?;
-        let l4 = l2
-            .get_l3()
-            .ok_or(ParseError::MissingL3)?
-            .get_l4()
-            .ok_or(ParseError::MissingL4)?;
-
-        Ok(match l4 {
-            L4Packet::Gre(gre) => {
-                if gre.get_routing_present() == 1 {
-                    return Err(ParseError::GreRoutingNotSupportedInPnet);
-                }
-                let outer_length = bytes.len() - gre.payload().len();
-                let inner_buffer = bytes
-                    .get(outer_length..)
-                    .ok_or(ParseError::InvalidProtocolAfterTunnel)?;
-                let after_tunnel = (EtherType(gre.get_protocol_type()), inner_buffer).try_into()?;
-
-                Packet::L3Tunnel(l2, after_tunnel)
+        Self::parse_with(bytes, &ParseOptions::default())
+    }
+}
+
+impl<'a> Packet<'a> {
+    /// Parse an ethernet packet, while enforcing the limits in `options`
+    pub fn parse_with(bytes: &'a [u8], options: &ParseOptions) -> Result<Self, ParseError> {
+        Self::from_link_type_with(LinkType::ETHERNET, bytes, options)
+    }
+
+    /// Parse a packet that starts with the `link_type` header, like the records of a capture file
+    pub fn from_link_type(link_type: LinkType, bytes: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_link_type_with(link_type, bytes, &ParseOptions::default())
+    }
+
+    pub fn from_link_type_with(
+        link_type: LinkType,
+        bytes: &'a [u8],
+        options: &ParseOptions,
+    ) -> Result<Self, ParseError> {
+        let l2 = match link_type {
+            LinkType::ETHERNET => L2Packet::parse_with(bytes, options)?,
+            LinkType::LINUX_SLL => L2Packet::parse_sll_with(bytes, options)?,
+            LinkType::LINUX_SLL2 => L2Packet::parse_sll2_with(bytes, options)?,
+            LinkType::NULL => L2Packet::parse_null_with(bytes, options)?,
+            LinkType::LOOP => L2Packet::parse_loop_with(bytes, options)?,
+            LinkType::RAW => return Self::from_ip_with(bytes, options),
+            LinkType::IPV4 => return Self::from_l3_with(EtherTypes::Ipv4, bytes, options),
+            LinkType::IPV6 => return Self::from_l3_with(EtherTypes::Ipv6, bytes, options),
+            _ => return Err(ParseError::UnsupportedLinkType(link_type)),
+        };
+        let l3_bytes = l2_payload(&l2, bytes);
+        Self::decapsulate(bytes, Level::L2(None, l2), l3_bytes, options)
+    }
+
+    /// Parse a raw IP packet, the version is taken from its first nibble
+    pub fn from_ip(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_ip_with(bytes, &ParseOptions::default())
+    }
+
+    pub fn from_ip_with(bytes: &'a [u8], options: &ParseOptions) -> Result<Self, ParseError> {
+        let version = bytes.first().map_or(0, |byte| byte >> 4);
+        let ether_type = ip_ether_type(version).ok_or(ParseError::InvalidIpVersion(version))?;
+        Self::from_l3_with(ether_type, bytes, options)
+    }
+
+    fn from_l3_with(
+        ether_type: EtherType,
+        bytes: &'a [u8],
+        options: &ParseOptions,
+    ) -> Result<Self, ParseError> {
+        let l3 = L3Packet::parse_with(ether_type, bytes, options)?;
+        let l3_bytes = &bytes[..l3.get_length()];
+        Self::decapsulate(bytes, Level::L3(None, l3), l3_bytes, options)
+    }
+
+    /// Parses the inner levels of the tunnels, starting from the outer level.
+    /// `l3_bytes` are the bytes of the L3 packet of the outer level, which was parsed from `bytes`
+    fn decapsulate(
+        bytes: &'a [u8],
+        outer: Level<'a>,
+        mut l3_bytes: &'a [u8],
+        options: &ParseOptions,
+    ) -> Result<Self, ParseError> {
+        let mut levels = Levels::new();
+        levels.push(outer);
+        while let Some((inner, inner_l3_bytes)) = parse_tunnel(
+            levels[levels.len() - 1].get_l3(),
+            l3_bytes,
+            levels.len() - 1,
+            options,
+        )? {
+            levels.push(inner);
+            l3_bytes = inner_l3_bytes;
+        }
+        Ok(Self { bytes, levels })
+    }
+}
+
+/// Ether type of the IP `version` (the first nibble of an IP packet)
+fn ip_ether_type(version: u8) -> Option<EtherType> {
+    match version {
+        4 => Some(EtherTypes::Ipv4),
+        6 => Some(EtherTypes::Ipv6),
+        _ => None,
+    }
+}
+
+/// Bytes of the L3 packet of `l2`, which was parsed from `bytes`
+fn l2_payload<'a>(l2: &L2Packet, bytes: &'a [u8]) -> &'a [u8] {
+    &bytes[l2.get_header().len()..bytes.len() - l2.get_trailer().len()]
+}
+
+/// `part` as a slice of `bytes`, which it was parsed from, to keep the lifetime of `bytes`
+fn sub_slice<'a>(bytes: &'a [u8], part: &[u8]) -> Option<&'a [u8]> {
+    let start = (part.as_ptr() as usize).checked_sub(bytes.as_ptr() as usize)?;
+    bytes.get(start..start + part.len())
+}
+
+/// Parses the inner level of a tunnel, if `outer_l3` is one.
+/// `l3_bytes` are the bytes of `outer_l3`, which the inner level borrows from,
+/// and `depth` is the amount of tunnels that were already decapsulated.
+/// Returns the inner level, and the bytes of its L3 packet
+fn parse_tunnel<'a>(
+    outer_l3: Option<&L3Packet>,
+    l3_bytes: &'a [u8],
+    depth: usize,
+    options: &ParseOptions,
+) -> Result<Option<(Level<'a>, &'a [u8])>, ParseError> {
+    // an ethernet pseudowire is carried right after the MPLS labels, without an L4
+    if let Some(L3Packet::Mpls(_, MplsPayload::Ethernet(_, frame))) = outer_l3 {
+        if options.max_tunnel_depth == Some(depth) {
+            return Err(ParseError::TunnelDepthExceeded);
+        }
+        let inner_buffer =
+            sub_slice(l3_bytes, frame.packet()).ok_or(ParseError::InvalidProtocolAfterTunnel)?;
+        let inner_l2 = L2Packet::parse_with(inner_buffer, options)?;
+        let inner_l3_bytes = l2_payload(&inner_l2, inner_buffer);
+        return Ok(Some((Level::L2(None, inner_l2), inner_l3_bytes)));
+    }
+    // packets without an L4 (arp, unknown ethertypes) are valid, they just can't be tunnels
+    let Some(l4) = outer_l3.and_then(|l3| l3.get_l4()) else {
+        return Ok(None);
+    };
+    let tunnel_payload = match l4 {
+        L4Packet::Gre(gre) => gre.payload(),
+        L4Packet::Udp(udp)
+            if options.vxlan_ports.contains(&udp.get_destination())
+                || options.geneve_ports.contains(&udp.get_destination()) =>
+        {
+            udp.payload()
+        }
+        // IP in IP has no tunnel header, the inner IP packet is the payload of the outer one
+        L4Packet::Unknown(IpNextHeaderProtocols::Ipv4 | IpNextHeaderProtocols::Ipv6, payload) => {
+            payload
+        }
+        _ => return Ok(None),
+    };
+    // the payload is taken from the l3 bytes to keep their lifetime. It doesn't always end
+    // with them, an outer container (like PPPoE) may be longer than the IP packet in it
+    let inner_buffer =
+        sub_slice(l3_bytes, tunnel_payload).ok_or(ParseError::InvalidProtocolAfterTunnel)?;
+    let (encapsulation, ether_type, inner_buffer) = match l4 {
+        L4Packet::Gre(gre) => match gre.get_protocol_type() {
+            ERSPAN_TYPE_II if !gre.get_sequence_present() => {
+                (None, TRANSPARENT_ETHERNET_BRIDGING, inner_buffer)
+            }
+            protocol_type @ (ERSPAN_TYPE_II | ERSPAN_TYPE_III) => {
+                let version = match protocol_type {
+                    ERSPAN_TYPE_II => erspan::VERSION_TYPE_II,
+                    _ => erspan::VERSION_TYPE_III,
+                };
+                let erspan = ErspanPacket::new(inner_buffer, version).ok_or(ParseError::Erspan)?;
+                let payload = &inner_buffer[erspan.get_header_length()..];
+                let ether_type = match erspan.get_frame_type() {
+                    None | Some(FRAME_TYPE_ETHERNET) => TRANSPARENT_ETHERNET_BRIDGING,
+                    Some(FRAME_TYPE_IP) => {
+                        match payload.first().and_then(|byte| ip_ether_type(byte >> 4)) {
+                            Some(ether_type) => ether_type,
+                            None => return Ok(None),
+                        }
+                    }
+                    // other frame types (like fibre channel) aren't decapsulated
+                    Some(_) => return Ok(None),
+                };
+                (Some(Encapsulation::Erspan(erspan)), ether_type, payload)
             }
-            _ => Packet::Regular(l2),
-        })
+            protocol_type => (None, protocol_type, inner_buffer),
+        },
+        L4Packet::Unknown(IpNextHeaderProtocols::Ipv4, _) => (None, EtherTypes::Ipv4, inner_buffer),
+        L4Packet::Unknown(IpNextHeaderProtocols::Ipv6, _) => (None, EtherTypes::Ipv6, inner_buffer),
+        L4Packet::Udp(udp) if options.vxlan_ports.contains(&udp.get_destination()) => {
+            let Some(vxlan) = VxlanPacket::new(inner_buffer).filter(VxlanPacket::is_vni_valid)
+            else {
+                return Ok(None);
+            };
+            let payload = &inner_buffer[VXLAN_HEADER_LENGTH..];
+            (
+                Some(Encapsulation::Vxlan(vxlan)),
+                TRANSPARENT_ETHERNET_BRIDGING,
+                payload,
+            )
+        }
+        _ => {
+            let Some(geneve) =
+                GenevePacket::new(inner_buffer).filter(|geneve| geneve.get_version() == 0)
+            else {
+                return Ok(None);
+            };
+            if ParseOptions::exceeds(geneve.get_options(), options.max_options) {
+                return Err(ParseError::TooManyGeneveOptions);
+            }
+            let payload = &inner_buffer[geneve.get_header_length()..];
+            let protocol_type = geneve.get_protocol_type();
+            (Some(Encapsulation::Geneve(geneve)), protocol_type, payload)
+        }
+    };
+    if options.max_tunnel_depth == Some(depth) {
+        return Err(ParseError::TunnelDepthExceeded);
+    }
+    // the UDP port doesn't prove that the payload is a tunnel, so payloads that don't parse
+    // are kept as the UDP payload (but the limits of the options still apply to tunnels)
+    let udp_tunnel = matches!(
+        encapsulation,
+        Some(Encapsulation::Vxlan(_) | Encapsulation::Geneve(_))
+    );
+    let inner = match ether_type {
+        TRANSPARENT_ETHERNET_BRIDGING => L2Packet::parse_with(inner_buffer, options)
+            .map(|inner_l2| {
+                let inner_l3_bytes = l2_payload(&inner_l2, inner_buffer);
+                (Level::L2(encapsulation, inner_l2), inner_l3_bytes)
+            })
+            .map_err(ParseError::from),
+        ether_type => L3Packet::parse_with(ether_type, inner_buffer, options)
+            .map(|inner_l3| {
+                let inner_l3_bytes = &inner_buffer[..inner_l3.get_length()];
+                (Level::L3(encapsulation, inner_l3), inner_l3_bytes)
+            })
+            .map_err(ParseError::from),
+    };
+    match inner {
+        Err(error) if udp_tunnel && !is_limit_error(&error) => Ok(None),
+        // unsupported GRE protocols are kept as the GRE payload, like other unknown payloads
+        Ok((Level::L3(None, L3Packet::Unknown(..)), _)) if matches!(l4, L4Packet::Gre(_)) => {
+            Ok(None)
+        }
+        inner => inner.map(Some),
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+/// Errors of the limits in [`ParseOptions`] (including `strict_ip_length`),
+/// as opposed to errors of malformed headers
+fn is_limit_error(error: &ParseError) -> bool {
+    let l3_error = match error {
+        ParseError::L2Error(l2::ParseError::TooManyVlans) => return true,
+        ParseError::L2Error(l2::ParseError::L3Error(error)) | ParseError::L3Error(error) => error,
+        _ => return false,
+    };
+    matches!(
+        l3_error,
+        l3::ParseError::TooManyIpv4Options
+            | l3::ParseError::TooManyMplsLabels
+            | l3::ParseError::TruncatedIPv4
+            | l3::ParseError::TruncatedIPv6
+            | l3::ParseError::L4Error(l4::ParseError::TooManyTcpOptions)
+            | l3::ParseError::Ipv6ExtensionError(ipv6_extensions::ParseError::TooManyExtensions)
+    )
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum HeaderPosition {
+    /// The first inner level (the level after the outer one)
     Inner,
     Outer,
+    /// The innermost header of each layer, which may be in different levels
+    /// (the innermost L2 of an IP in GRE packet is the outer ethernet)
     Innermost,
+    /// The level at this index, the outer level is 0
+    Nth(usize),
+}
+
+impl<'a> Level<'a> {
+    pub fn get_l2(&self) -> Option<&L2Packet<'a>> {
+        match self {
+            Level::L2(_, l2) => Some(l2),
+            Level::L3(_, _) => None,
+        }
+    }
+
+    pub fn get_l3(&self) -> Option<&L3Packet<'a>> {
+        match self {
+            Level::L2(_, l2) => l2.get_l3(),
+            Level::L3(_, l3) => Some(l3),
+        }
+    }
+
+    pub fn get_l4(&self) -> Option<&L4Packet<'a>> {
+        self.get_l3()?.get_l4()
+    }
+
+    pub fn get_encapsulation(&self) -> Option<&Encapsulation<'a>> {
+        match self {
+            Level::L2(encapsulation, _) | Level::L3(encapsulation, _) => encapsulation.as_ref(),
+        }
+    }
 }
 
 impl<'a> Packet<'a> {
+    /// The encapsulation levels, from the outer to the innermost
+    pub fn get_levels(&self) -> &[Level<'a>] {
+        &self.levels
+    }
+
+    /// The level at `position`, [`HeaderPosition::Innermost`] is the last level
+    pub fn get_level(&self, position: HeaderPosition) -> Option<&Level<'a>> {
+        match position {
+            HeaderPosition::Outer => self.levels.first(),
+            HeaderPosition::Inner => self.levels.get(1),
+            HeaderPosition::Innermost => self.levels.last(),
+            HeaderPosition::Nth(index) => self.levels.get(index),
+        }
+    }
+
     pub fn get_l2(&self, position: HeaderPosition) -> Option<&L2Packet<'a>> {
-        match (position, self) {
-            (HeaderPosition::Inner, _) => None, // no l2 encaps are supported atm
-            (HeaderPosition::Outer, Packet::Regular(l2)) => l2.into(),
-            (HeaderPosition::Outer, Packet::L3Tunnel(l2, _)) => l2.into(),
-            (HeaderPosition::Innermost, Packet::Regular(l2)) => l2.into(),
-            (HeaderPosition::Innermost, Packet::L3Tunnel(l2, _)) => l2.into(), // will change if we support l2 encaps
+        match position {
+            HeaderPosition::Innermost => self.levels.iter().rev().find_map(Level::get_l2),
+            position => self.get_level(position)?.get_l2(),
         }
     }
 
     pub fn get_l3(&self, position: HeaderPosition) -> Option<&L3Packet<'a>> {
-        match (position, self) {
-            (HeaderPosition::Inner, Packet::Regular(_)) => None,
-            (HeaderPosition::Inner, Packet::L3Tunnel(_, inner_l3)) => inner_l3.into(),
-            (HeaderPosition::Outer, Packet::Regular(l2)) => l2.get_l3(),
-            (HeaderPosition::Outer, Packet::L3Tunnel(l2, _)) => l2.get_l3(),
-            (HeaderPosition::Innermost, Packet::Regular(l2)) => l2.get_l3(),
-            (HeaderPosition::Innermost, Packet::L3Tunnel(_, inner_l3)) => inner_l3.into(),
+        match position {
+            HeaderPosition::Innermost => self.levels.iter().rev().find_map(Level::get_l3),
+            position => self.get_level(position)?.get_l3(),
         }
     }
 
     pub fn get_l4(&self, position: HeaderPosition) -> Option<&L4Packet<'a>> {
-        // this is correct unless we have an L4Tunnel someday
+        // the l4 of the innermost l3, and not the innermost l4
         self.get_l3(position)?.get_l4()
     }
 
+    /// Header of the tunnel (VXLAN, Geneve) that carried the level at `position`
+    pub fn get_encapsulation(&self, position: HeaderPosition) -> Option<&Encapsulation<'a>> {
+        match position {
+            HeaderPosition::Innermost => {
+                self.levels.iter().rev().find_map(Level::get_encapsulation)
+            }
+            position => self.get_level(position)?.get_encapsulation(),
+        }
+    }
+
+    /// All the bytes the packet was parsed from
+    pub fn get_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
     pub fn get_payload(&self) -> Option<&[u8]> {
         self.get_l4(HeaderPosition::Innermost)
-            .and_then(move |l4| match l4 {
-                L4Packet::Tcp(tcp) => Some(tcp.payload()),
-                L4Packet::Udp(udp) => Some(udp.payload()),
-                L4Packet::Gre(gre) => Some(gre.payload()),
-                L4Packet::Icmp(icmp) => Some(icmp.payload()),
-                L4Packet::Icmpv6(icmpv6) => Some(icmpv6.payload()),
+            .map(move |l4| match l4 {
+                L4Packet::Tcp(tcp) => tcp.payload(),
+                L4Packet::Udp(udp) => udp.payload(),
+                L4Packet::Gre(gre) => gre.payload(),
+                L4Packet::Icmp(icmp) => icmp.payload(),
+                L4Packet::Icmpv6(icmpv6) => icmpv6.payload(),
+                L4Packet::Unknown(_, payload) | L4Packet::Fragment(payload) => payload,
             })
     }
 }
 
 impl Display for Packet<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Packet::Regular(inner) => write!(f, "Packet: {}", inner),
-            Packet::L3Tunnel(outer, inner) => {
-                write!(f, "Encapsulated Packet: {} | {}", outer, inner)
+        match self.levels.len() {
+            1 => write!(f, "Packet: ")?,
+            _ => write!(f, "Encapsulated Packet: ")?,
+        }
+        for (index, level) in self.levels.iter().enumerate() {
+            if index != 0 {
+                write!(f, " | ")?;
             }
+            write!(f, "{}", level)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Level<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(encapsulation) = self.get_encapsulation() {
+            write!(f, "{} | ", encapsulation)?;
+        }
+        match self {
+            Level::L2(_, l2) => write!(f, "{}", l2),
+            Level::L3(_, l3) => write!(f, "{}", l3),
+        }
+    }
+}
+
+impl Display for Encapsulation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Encapsulation::Vxlan(vxlan) => write!(f, "VXLAN ({})", vxlan.get_vni()),
+            Encapsulation::Geneve(geneve) => write!(f, "Geneve ({})", geneve.get_vni()),
+            Encapsulation::Erspan(erspan) => write!(f, "ERSPAN ({})", erspan.get_session_id()),
         }
     }
 }
@@ -0,0 +1,68 @@
+use crate::protocols::{geneve::GENEVE_PORT, vxlan::VXLAN_PORT};
+use smallvec::{smallvec, SmallVec};
+
+/// Limits and policies applied while parsing a packet.
+///
+/// A crafted packet can contain a lot of stacked headers (vlans, extensions, tunnels, options),
+/// and parsing all of them slows the parser down. Every limit is optional, and a packet that
+/// goes over a limit fails to parse with a dedicated error.
+/// <br>
+/// The default options have no limits and accept truncated packets,
+/// which is what the `TryFrom` implementations use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Maximum amount of stacked vlan tags in an L2 header
+    pub max_vlan_tags: Option<usize>,
+    /// Maximum amount of IPv6 extension headers before the L4 header
+    pub max_ipv6_extensions: Option<usize>,
+    /// Maximum amount of tunnels that will be decapsulated
+    pub max_tunnel_depth: Option<usize>,
+    /// Maximum amount of IPv4 options, TCP options and Geneve options in a single header
+    pub max_options: Option<usize>,
+    /// Fail when an IP header declares a length larger than the captured bytes,
+    /// instead of parsing the packet as truncated
+    pub strict_ip_length: bool,
+    /// Maximum amount of labels in an MPLS label stack
+    pub max_mpls_labels: Option<usize>,
+    /// How non-IP payloads after the bottom MPLS label are parsed
+    pub mpls_pseudowire: MplsPseudowire,
+    /// UDP destination ports that carry VXLAN
+    pub vxlan_ports: SmallVec<[u16; 2]>,
+    /// UDP destination ports that carry Geneve
+    pub geneve_ports: SmallVec<[u16; 2]>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_vlan_tags: None,
+            max_ipv6_extensions: None,
+            max_tunnel_depth: None,
+            max_options: None,
+            strict_ip_length: false,
+            max_mpls_labels: None,
+            mpls_pseudowire: MplsPseudowire::default(),
+            vxlan_ports: smallvec![VXLAN_PORT],
+            geneve_ports: smallvec![GENEVE_PORT],
+        }
+    }
+}
+
+/// MPLS doesn't say what's after the label stack, so IP is detected by the first nibble,
+/// and anything else is parsed according to this setting
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MplsPseudowire {
+    /// Non-IP payloads are kept as unknown
+    #[default]
+    None,
+    /// Non-IP payloads are ethernet pseudowires (RFC 4448),
+    /// optionally starting with a 4 bytes control word
+    Ethernet { control_word: bool },
+}
+
+impl ParseOptions {
+    /// Checks if an iterator yields more items than the given limit (without collecting it)
+    pub(crate) fn exceeds<I: Iterator>(mut iterator: I, limit: Option<usize>) -> bool {
+        limit.is_some_and(|limit| iterator.nth(limit).is_some())
+    }
+}
@@ -0,0 +1,151 @@
+use pnet::packet::{ethernet::EtherType, Packet};
+
+/// GRE protocol of ERSPAN type I and II, type I has no sequence number and no ERSPAN header
+pub const ERSPAN_TYPE_II: EtherType = EtherType(0x88be);
+/// GRE protocol of ERSPAN type III
+pub const ERSPAN_TYPE_III: EtherType = EtherType(0x22eb);
+pub const ERSPAN_TYPE_II_HEADER_LENGTH: usize = 8;
+pub const ERSPAN_TYPE_III_HEADER_LENGTH: usize = 12;
+pub const PLATFORM_SUBHEADER_LENGTH: usize = 8;
+pub const VERSION_TYPE_II: u8 = 1;
+pub const VERSION_TYPE_III: u8 = 2;
+/// Type III frame type of a mirrored ethernet frame
+pub const FRAME_TYPE_ETHERNET: u8 = 0;
+/// Type III frame type of a mirrored IP packet (without its L2 header)
+pub const FRAME_TYPE_IP: u8 = 2;
+/// In the last byte of a type III header
+const FLAG_PLATFORM_SUBHEADER: u8 = 0x01;
+
+/// Zero-copy view of an ERSPAN type II or type III header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErspanPacket<'a> {
+    bytes: &'a [u8],
+    header_length: usize,
+}
+
+impl<'a> ErspanPacket<'a> {
+    /// Parses a header of the `version` that the GRE protocol declared,
+    /// headers of another version are rejected
+    pub fn new(bytes: &'a [u8], version: u8) -> Option<Self> {
+        if bytes.first()? >> 4 != version {
+            return None;
+        }
+        let header_length = match version {
+            VERSION_TYPE_II => ERSPAN_TYPE_II_HEADER_LENGTH,
+            VERSION_TYPE_III => {
+                let flags = bytes.get(ERSPAN_TYPE_III_HEADER_LENGTH - 1)?;
+                match flags & FLAG_PLATFORM_SUBHEADER {
+                    0 => ERSPAN_TYPE_III_HEADER_LENGTH,
+                    _ => ERSPAN_TYPE_III_HEADER_LENGTH + PLATFORM_SUBHEADER_LENGTH,
+                }
+            }
+            _ => return None,
+        };
+        bytes.get(..header_length)?;
+        Some(Self {
+            bytes,
+            header_length,
+        })
+    }
+
+    /// 1 for type II, 2 for type III
+    pub fn get_version(&self) -> u8 {
+        self.bytes[0] >> 4
+    }
+
+    /// VLAN of the mirrored frame
+    pub fn get_vlan(&self) -> u16 {
+        u16::from_be_bytes([self.bytes[0] & 0x0f, self.bytes[1]])
+    }
+
+    pub fn get_cos(&self) -> u8 {
+        self.bytes[2] >> 5
+    }
+
+    /// Whether the mirrored frame was truncated
+    pub fn get_truncated(&self) -> bool {
+        self.bytes[2] & 0x04 != 0
+    }
+
+    /// 10 bits session id
+    pub fn get_session_id(&self) -> u16 {
+        u16::from_be_bytes([self.bytes[2] & 0x03, self.bytes[3]])
+    }
+
+    /// Type II, the VLAN encapsulation of the mirrored frame
+    pub fn get_encapsulation_type(&self) -> Option<u8> {
+        self.is_type_ii().then(|| (self.bytes[2] >> 3) & 0b11)
+    }
+
+    /// Type II, 20 bits port index
+    pub fn get_index(&self) -> Option<u32> {
+        self.is_type_ii()
+            .then(|| u32::from_be_bytes([0, self.bytes[5] & 0x0f, self.bytes[6], self.bytes[7]]))
+    }
+
+    /// Type III, bad or short frame status of the mirrored frame
+    pub fn get_bso(&self) -> Option<u8> {
+        self.is_type_iii().then(|| (self.bytes[2] >> 3) & 0b11)
+    }
+
+    /// Type III, in the units of `get_granularity`
+    pub fn get_timestamp(&self) -> Option<u32> {
+        self.is_type_iii()
+            .then(|| u32::from_be_bytes(self.bytes[4..8].try_into().unwrap()))
+    }
+
+    /// Type III, security group tag
+    pub fn get_sgt(&self) -> Option<u16> {
+        self.is_type_iii()
+            .then(|| u16::from_be_bytes([self.bytes[8], self.bytes[9]]))
+    }
+
+    /// Type III, the frame type of the mirrored frame (`FRAME_TYPE_ETHERNET` or `FRAME_TYPE_IP`)
+    pub fn get_frame_type(&self) -> Option<u8> {
+        self.is_type_iii().then(|| (self.bytes[10] >> 2) & 0x1f)
+    }
+
+    /// Type III, 6 bits id of the mirroring hardware
+    pub fn get_hardware_id(&self) -> Option<u8> {
+        self.is_type_iii()
+            .then(|| (self.bytes[10] & 0b11) << 4 | self.bytes[11] >> 4)
+    }
+
+    /// Type III, whether the frame was mirrored on egress (or on ingress)
+    pub fn get_direction(&self) -> Option<bool> {
+        self.is_type_iii().then(|| self.bytes[11] & 0x08 != 0)
+    }
+
+    /// Type III, the granularity of the timestamp
+    pub fn get_granularity(&self) -> Option<u8> {
+        self.is_type_iii().then(|| (self.bytes[11] >> 1) & 0b11)
+    }
+
+    /// Type III, the platform specific subheader
+    pub fn get_platform_subheader(&self) -> Option<&'a [u8]> {
+        (self.is_type_iii() && self.bytes[11] & FLAG_PLATFORM_SUBHEADER != 0)
+            .then(|| &self.bytes[ERSPAN_TYPE_III_HEADER_LENGTH..self.header_length])
+    }
+
+    pub fn get_header_length(&self) -> usize {
+        self.header_length
+    }
+
+    fn is_type_ii(&self) -> bool {
+        self.get_version() == VERSION_TYPE_II
+    }
+
+    fn is_type_iii(&self) -> bool {
+        self.get_version() == VERSION_TYPE_III
+    }
+}
+
+impl Packet for ErspanPacket<'_> {
+    fn packet(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.bytes[self.header_length..]
+    }
+}
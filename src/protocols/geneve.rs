@@ -0,0 +1,106 @@
+use pnet::packet::{ethernet::EtherType, Packet};
+
+pub const GENEVE_PORT: u16 = 6081;
+pub const GENEVE_HEADER_LENGTH: usize = 8;
+const OPTION_HEADER_LENGTH: usize = 4;
+const FLAG_OAM: u8 = 0x80;
+const FLAG_CRITICAL: u8 = 0x40;
+/// The high bit of the option type marks critical options
+const OPTION_TYPE_CRITICAL: u8 = 0x80;
+
+/// Zero-copy view of a Geneve header (RFC 8926), with its options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenevePacket<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> GenevePacket<'a> {
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        let options_length = (*bytes.first()? & 0x3f) as usize * 4;
+        bytes.get(..GENEVE_HEADER_LENGTH + options_length)?;
+        Some(Self { bytes })
+    }
+
+    pub fn get_version(&self) -> u8 {
+        self.bytes[0] >> 6
+    }
+
+    /// Length of the options, in 4 bytes words
+    pub fn get_options_length(&self) -> u8 {
+        self.bytes[0] & 0x3f
+    }
+
+    /// Control packet (OAM), which shouldn't be forwarded as data
+    pub fn get_oam(&self) -> bool {
+        self.bytes[1] & FLAG_OAM != 0
+    }
+
+    /// Critical options are present
+    pub fn get_critical(&self) -> bool {
+        self.bytes[1] & FLAG_CRITICAL != 0
+    }
+
+    pub fn get_protocol_type(&self) -> EtherType {
+        EtherType(u16::from_be_bytes([self.bytes[2], self.bytes[3]]))
+    }
+
+    /// 24 bits virtual network identifier
+    pub fn get_vni(&self) -> u32 {
+        u32::from_be_bytes([0, self.bytes[4], self.bytes[5], self.bytes[6]])
+    }
+
+    pub fn get_header_length(&self) -> usize {
+        GENEVE_HEADER_LENGTH + self.get_options_length() as usize * 4
+    }
+
+    pub fn get_options(&self) -> GeneveOptionsIterator<'a> {
+        GeneveOptionsIterator {
+            bytes: &self.bytes[GENEVE_HEADER_LENGTH..self.get_header_length()],
+        }
+    }
+}
+
+impl Packet for GenevePacket<'_> {
+    fn packet(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.bytes[self.get_header_length()..]
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GeneveOption<'a> {
+    pub class: u16,
+    pub option_type: u8,
+    pub data: &'a [u8],
+}
+
+impl GeneveOption<'_> {
+    pub fn is_critical(&self) -> bool {
+        self.option_type & OPTION_TYPE_CRITICAL != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneveOptionsIterator<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for GeneveOptionsIterator<'a> {
+    type Item = GeneveOption<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.bytes.get(..OPTION_HEADER_LENGTH)?;
+        // the option length doesn't include its header, and is in 4 bytes words
+        let total_length = OPTION_HEADER_LENGTH + (header[3] & 0x1f) as usize * 4;
+        let option = GeneveOption {
+            class: u16::from_be_bytes([header[0], header[1]]),
+            option_type: header[2],
+            data: self.bytes.get(OPTION_HEADER_LENGTH..total_length)?,
+        };
+        self.bytes = &self.bytes[total_length..];
+        Some(option)
+    }
+}
@@ -0,0 +1,258 @@
+use pnet::packet::{ethernet::EtherType, util, Packet};
+
+use crate::l2::TRANSPARENT_ETHERNET_BRIDGING;
+
+const GRE_BASE_LENGTH: usize = 4;
+const SOURCE_ROUTE_ENTRY_HEADER_LENGTH: usize = 4;
+const FLAG_CHECKSUM: u8 = 0x80;
+const FLAG_ROUTING: u8 = 0x40;
+const FLAG_KEY: u8 = 0x20;
+const FLAG_SEQUENCE: u8 = 0x10;
+const FLAG_STRICT_SOURCE_ROUTE: u8 = 0x08;
+/// In the second byte, only used by enhanced GRE (version 1, PPTP)
+const FLAG_ACKNOWLEDGEMENT: u8 = 0x80;
+const VERSION_ENHANCED: u8 = 1;
+
+/// Zero-copy view of a GRE header (RFC 1701, RFC 2784, RFC 2890),
+/// and of the enhanced GRE header of PPTP (RFC 2637)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrePacket<'a> {
+    bytes: &'a [u8],
+    header_length: usize,
+}
+
+impl<'a> GrePacket<'a> {
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        let base = bytes.get(..GRE_BASE_LENGTH)?;
+        let (flags, version_flags) = (base[0], base[1]);
+        let mut header_length = GRE_BASE_LENGTH;
+        for present in [
+            flags & (FLAG_CHECKSUM | FLAG_ROUTING) != 0,
+            flags & FLAG_KEY != 0,
+            flags & FLAG_SEQUENCE != 0,
+            is_acknowledgement_present(version_flags),
+        ] {
+            if present {
+                header_length += 4;
+            }
+        }
+        bytes.get(..header_length)?;
+        if flags & FLAG_ROUTING != 0 {
+            // the source route entries end with a null entry
+            let mut entries = GreSourceRouteIterator {
+                bytes: &bytes[header_length..],
+                terminated: false,
+            };
+            header_length += entries
+                .by_ref()
+                .map(|entry| entry.get_length())
+                .sum::<usize>();
+            if !entries.terminated {
+                return None;
+            }
+            header_length += SOURCE_ROUTE_ENTRY_HEADER_LENGTH;
+        }
+        Some(Self {
+            bytes,
+            header_length,
+        })
+    }
+
+    pub fn get_checksum_present(&self) -> bool {
+        self.bytes[0] & FLAG_CHECKSUM != 0
+    }
+
+    pub fn get_routing_present(&self) -> bool {
+        self.bytes[0] & FLAG_ROUTING != 0
+    }
+
+    pub fn get_key_present(&self) -> bool {
+        self.bytes[0] & FLAG_KEY != 0
+    }
+
+    pub fn get_sequence_present(&self) -> bool {
+        self.bytes[0] & FLAG_SEQUENCE != 0
+    }
+
+    pub fn get_strict_source_route(&self) -> bool {
+        self.bytes[0] & FLAG_STRICT_SOURCE_ROUTE != 0
+    }
+
+    pub fn get_recursion_control(&self) -> u8 {
+        self.bytes[0] & 0b111
+    }
+
+    /// The acknowledgement flag is reserved in other versions than enhanced GRE (RFC 2637)
+    pub fn get_acknowledgement_present(&self) -> bool {
+        is_acknowledgement_present(self.bytes[1])
+    }
+
+    pub fn get_version(&self) -> u8 {
+        self.bytes[1] & 0b111
+    }
+
+    pub fn get_protocol_type(&self) -> EtherType {
+        EtherType(u16::from_be_bytes([self.bytes[2], self.bytes[3]]))
+    }
+
+    /// The checksum and offset fields are present if either the checksum or the routing flags are set
+    pub fn get_checksum(&self) -> Option<u16> {
+        self.get_checksum_present()
+            .then(|| u16::from_be_bytes([self.bytes[4], self.bytes[5]]))
+    }
+
+    /// Offset of the active source route entry, from the start of the routing field
+    pub fn get_offset(&self) -> Option<u16> {
+        self.get_routing_present()
+            .then(|| u16::from_be_bytes([self.bytes[6], self.bytes[7]]))
+    }
+
+    /// The key of GRE, or the payload length and call id of enhanced GRE
+    pub fn get_key(&self) -> Option<u32> {
+        self.get_key_present()
+            .then(|| self.read_u32(self.key_offset()))
+    }
+
+    /// Whether this is NVGRE (RFC 7637): ethernet over GRE, with the key holding the VSID
+    pub fn is_nvgre(&self) -> bool {
+        self.get_protocol_type() == TRANSPARENT_ETHERNET_BRIDGING
+            && self.get_key_present()
+            && !self.get_checksum_present()
+            && !self.get_routing_present()
+            && !self.get_sequence_present()
+    }
+
+    /// Virtual subnet id of NVGRE, the upper 24 bits of the key
+    pub fn get_vsid(&self) -> Option<u32> {
+        Some(self.get_key().filter(|_| self.is_nvgre())? >> 8)
+    }
+
+    /// Flow id of NVGRE, the lower 8 bits of the key
+    pub fn get_flow_id(&self) -> Option<u8> {
+        Some(self.get_key().filter(|_| self.is_nvgre())? as u8)
+    }
+
+    pub fn get_sequence(&self) -> Option<u32> {
+        self.get_sequence_present()
+            .then(|| self.read_u32(self.key_offset() + self.field_length(FLAG_KEY)))
+    }
+
+    /// Acknowledgement number of enhanced GRE
+    pub fn get_acknowledgement(&self) -> Option<u32> {
+        self.get_acknowledgement_present()
+            .then(|| self.read_u32(self.routing_offset() - 4))
+    }
+
+    /// Source route entries, without the null entry that ends them
+    pub fn get_routing(&self) -> GreSourceRouteIterator<'a> {
+        GreSourceRouteIterator {
+            bytes: &self.bytes[self.routing_offset()..self.header_length],
+            terminated: false,
+        }
+    }
+
+    /// Checks the checksum of the header and payload, `None` if there is no checksum
+    pub fn verify_checksum(&self) -> Option<bool> {
+        let checksum = self.get_checksum()?;
+        // the checksum is the 3rd 16 bits word
+        Some(util::checksum(self.bytes, 2) == checksum)
+    }
+
+    pub fn get_header_length(&self) -> usize {
+        self.header_length
+    }
+
+    fn key_offset(&self) -> usize {
+        GRE_BASE_LENGTH + self.field_length(FLAG_CHECKSUM | FLAG_ROUTING)
+    }
+
+    fn field_length(&self, flags: u8) -> usize {
+        match self.bytes[0] & flags {
+            0 => 0,
+            _ => 4,
+        }
+    }
+
+    fn routing_offset(&self) -> usize {
+        self.key_offset()
+            + self.field_length(FLAG_KEY)
+            + self.field_length(FLAG_SEQUENCE)
+            + match self.get_acknowledgement_present() {
+                true => 4,
+                false => 0,
+            }
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        u32::from_be_bytes(self.bytes[offset..offset + 4].try_into().unwrap())
+    }
+}
+
+fn is_acknowledgement_present(version_flags: u8) -> bool {
+    version_flags & 0b111 == VERSION_ENHANCED && version_flags & FLAG_ACKNOWLEDGEMENT != 0
+}
+
+impl Packet for GrePacket<'_> {
+    fn packet(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.bytes[self.header_length..]
+    }
+}
+
+/// Zero-copy view of a GRE source route entry (RFC 1701)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreSourceRouteEntry<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> GreSourceRouteEntry<'a> {
+    pub fn get_address_family(&self) -> u16 {
+        u16::from_be_bytes([self.bytes[0], self.bytes[1]])
+    }
+
+    /// Offset of the active entry in the routing information
+    pub fn get_sre_offset(&self) -> u8 {
+        self.bytes[2]
+    }
+
+    /// Length of the routing information
+    pub fn get_sre_length(&self) -> u8 {
+        self.bytes[3]
+    }
+
+    pub fn get_routing_information(&self) -> &'a [u8] {
+        &self.bytes[SOURCE_ROUTE_ENTRY_HEADER_LENGTH..]
+    }
+
+    /// Length of the entry, with its header
+    fn get_length(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+pub struct GreSourceRouteIterator<'a> {
+    bytes: &'a [u8],
+    terminated: bool,
+}
+
+impl<'a> Iterator for GreSourceRouteIterator<'a> {
+    type Item = GreSourceRouteEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.bytes.get(..SOURCE_ROUTE_ENTRY_HEADER_LENGTH)?;
+        let address_family = u16::from_be_bytes([header[0], header[1]]);
+        let length = header[3] as usize;
+        if address_family == 0 && length == 0 {
+            self.terminated = true;
+            return None;
+        }
+        let (entry, rest) = self
+            .bytes
+            .split_at_checked(SOURCE_ROUTE_ENTRY_HEADER_LENGTH + length)?;
+        self.bytes = rest;
+        Some(GreSourceRouteEntry { bytes: entry })
+    }
+}
@@ -0,0 +1,123 @@
+use pnet::packet::{ethernet::EtherType, Packet};
+
+/// Service access points of the LLC header
+pub mod saps {
+    pub const STP: u8 = 0x42;
+    pub const SNAP: u8 = 0xaa;
+    pub const IPX: u8 = 0xe0;
+    pub const NETBIOS: u8 = 0xf0;
+}
+
+/// SNAP OUI of encapsulated ethernet (RFC 1042), the protocol id is an ethertype
+pub const OUI_ENCAPSULATED_ETHERNET: u32 = 0x000000;
+/// SNAP OUI of 802.1H bridge tunnel, the protocol id is an ethertype
+pub const OUI_BRIDGE_TUNNEL: u32 = 0x0000f8;
+pub const OUI_CISCO: u32 = 0x00000c;
+/// SNAP protocol id of CDP, under the Cisco OUI
+pub const CDP_PROTOCOL_ID: u16 = 0x2000;
+
+const SNAP_LENGTH: usize = 5;
+
+/// Zero-copy view of an 802.2 LLC header, with its SNAP extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlcPacket<'a> {
+    bytes: &'a [u8],
+    header_length: usize,
+}
+
+impl<'a> LlcPacket<'a> {
+    /// `bytes` should be bounded by the 802.3 length
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        let control = *bytes.get(2)?;
+        // only unnumbered frames have a 1 byte control field
+        let mut header_length = match control & 0b11 {
+            0b11 => 3,
+            _ => 4,
+        };
+        if bytes[0] == saps::SNAP && bytes[1] == saps::SNAP {
+            header_length += SNAP_LENGTH;
+        }
+        bytes.get(..header_length)?;
+        Some(Self {
+            bytes,
+            header_length,
+        })
+    }
+
+    pub fn get_dsap(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    pub fn get_ssap(&self) -> u8 {
+        self.bytes[1]
+    }
+
+    /// 1 byte for unnumbered frames, 2 bytes for information and supervisory frames
+    pub fn get_control(&self) -> u16 {
+        match self.control_length() {
+            1 => self.bytes[2].into(),
+            _ => u16::from_le_bytes([self.bytes[2], self.bytes[3]]),
+        }
+    }
+
+    pub fn get_header_length(&self) -> usize {
+        self.header_length
+    }
+
+    pub fn is_snap(&self) -> bool {
+        self.header_length > 2 + self.control_length()
+    }
+
+    pub fn get_snap_oui(&self) -> Option<u32> {
+        let snap = self.snap()?;
+        Some(u32::from_be_bytes([0, snap[0], snap[1], snap[2]]))
+    }
+
+    pub fn get_snap_protocol_id(&self) -> Option<u16> {
+        let snap = self.snap()?;
+        Some(u16::from_be_bytes([snap[3], snap[4]]))
+    }
+
+    /// Ethertype of the payload, for SNAP frames that encapsulate ethernet protocols
+    pub fn get_ether_type(&self) -> Option<EtherType> {
+        match self.get_snap_oui()? {
+            OUI_ENCAPSULATED_ETHERNET | OUI_BRIDGE_TUNNEL => {
+                Some(EtherType(self.get_snap_protocol_id()?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Spanning tree BPDU
+    pub fn is_stp(&self) -> bool {
+        self.get_dsap() == saps::STP && self.get_ssap() == saps::STP
+    }
+
+    /// Cisco discovery protocol
+    pub fn is_cdp(&self) -> bool {
+        self.get_snap_oui() == Some(OUI_CISCO)
+            && self.get_snap_protocol_id() == Some(CDP_PROTOCOL_ID)
+    }
+
+    fn control_length(&self) -> usize {
+        match self.bytes[2] & 0b11 {
+            0b11 => 1,
+            _ => 2,
+        }
+    }
+
+    fn snap(&self) -> Option<&[u8]> {
+        self.is_snap()
+            .then(|| &self.bytes[self.header_length - SNAP_LENGTH..self.header_length])
+    }
+}
+
+impl Packet for LlcPacket<'_> {
+    fn packet(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.bytes[self.header_length..]
+    }
+}
@@ -0,0 +1,74 @@
+use pnet::packet::{
+    ethernet::{EtherType, EtherTypes},
+    Packet,
+};
+
+/// Address families of the loopback header.
+/// AF_INET6 has a different value in every OS, so all of them are accepted
+pub mod address_families {
+    pub const INET: u32 = 2;
+    pub const INET6_LINUX: u32 = 10;
+    pub const INET6_WINDOWS: u32 = 23;
+    pub const INET6_BSD: u32 = 24;
+    pub const INET6_FREEBSD: u32 = 28;
+    pub const INET6_DARWIN: u32 = 30;
+}
+
+pub const LOOPBACK_HEADER_LENGTH: usize = 4;
+
+/// Zero-copy view of the 4 bytes address family header of loopback captures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopbackPacket<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> LoopbackPacket<'a> {
+    /// `DLT_NULL` header, in the byte order of the capturing host.
+    /// Families are small numbers, so the byte order is detected from the value
+    pub fn new_null(bytes: &'a [u8]) -> Option<Self> {
+        let header: [u8; 4] = bytes.get(..LOOPBACK_HEADER_LENGTH)?.try_into().ok()?;
+        let big_endian = u32::from_le_bytes(header) & 0xffff_0000 != 0;
+        Some(Self { bytes, big_endian })
+    }
+
+    /// `DLT_LOOP` header, always in network byte order
+    pub fn new_loop(bytes: &'a [u8]) -> Option<Self> {
+        bytes.get(..LOOPBACK_HEADER_LENGTH)?;
+        Some(Self {
+            bytes,
+            big_endian: true,
+        })
+    }
+
+    pub fn get_family(&self) -> u32 {
+        let header = self.bytes[..LOOPBACK_HEADER_LENGTH].try_into().unwrap();
+        match self.big_endian {
+            true => u32::from_be_bytes(header),
+            false => u32::from_le_bytes(header),
+        }
+    }
+
+    /// Ethertype of the payload, for IPv4 and IPv6 families
+    pub fn get_ether_type(&self) -> Option<EtherType> {
+        match self.get_family() {
+            address_families::INET => Some(EtherTypes::Ipv4),
+            address_families::INET6_LINUX
+            | address_families::INET6_WINDOWS
+            | address_families::INET6_BSD
+            | address_families::INET6_FREEBSD
+            | address_families::INET6_DARWIN => Some(EtherTypes::Ipv6),
+            _ => None,
+        }
+    }
+}
+
+impl Packet for LoopbackPacket<'_> {
+    fn packet(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.bytes[LOOPBACK_HEADER_LENGTH..]
+    }
+}
@@ -0,0 +1,18 @@
+/// Encapsulated remote switched port analyzer (mirrored traffic over GRE)
+pub mod erspan;
+/// Generic network virtualization encapsulation
+pub mod geneve;
+/// Generic routing encapsulation
+pub mod gre;
+/// IEEE 802.2 LLC and SNAP
+pub mod llc;
+/// BSD loopback encapsulation (`DLT_NULL`, `DLT_LOOP`)
+pub mod loopback;
+/// MPLS label stack entries
+pub mod mpls;
+/// Point-to-Point protocol, with its control protocols
+pub mod ppp;
+/// PPP over Ethernet
+pub mod pppoe;
+/// Virtual extensible LAN
+pub mod vxlan;
@@ -0,0 +1,48 @@
+use pnet::packet::Packet;
+
+pub const MPLS_LABEL_LENGTH: usize = 4;
+
+/// Zero-copy view of an MPLS label stack entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MplsLabel<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> MplsLabel<'a> {
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        bytes.get(..MPLS_LABEL_LENGTH)?;
+        Some(Self { bytes })
+    }
+
+    fn entry(&self) -> u32 {
+        u32::from_be_bytes(self.bytes[..MPLS_LABEL_LENGTH].try_into().unwrap())
+    }
+
+    /// 20 bits label value
+    pub fn get_label(&self) -> u32 {
+        self.entry() >> 12
+    }
+
+    /// Traffic class (formerly EXP) bits
+    pub fn get_traffic_class(&self) -> u8 {
+        ((self.entry() >> 9) & 0b111) as u8
+    }
+
+    pub fn get_bottom_of_stack(&self) -> bool {
+        self.entry() & 0x100 != 0
+    }
+
+    pub fn get_ttl(&self) -> u8 {
+        self.entry() as u8
+    }
+}
+
+impl Packet for MplsLabel<'_> {
+    fn packet(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.bytes[MPLS_LABEL_LENGTH..]
+    }
+}
@@ -0,0 +1,287 @@
+use pnet::packet::Packet;
+
+const CONTROL_HEADER_LENGTH: usize = 4;
+const OPTION_HEADER_LENGTH: usize = 2;
+
+/// Protocol numbers of PPP packets
+pub mod protocols {
+    pub const IPV4: u16 = 0x0021;
+    pub const IPV6: u16 = 0x0057;
+    pub const IPCP: u16 = 0x8021;
+    pub const IPV6CP: u16 = 0x8057;
+    pub const LCP: u16 = 0xc021;
+    pub const PAP: u16 = 0xc023;
+    pub const CHAP: u16 = 0xc223;
+}
+
+/// Codes of LCP and of the network control protocols (IPCP, IPV6CP)
+pub mod control_codes {
+    pub const CONFIGURE_REQUEST: u8 = 1;
+    pub const CONFIGURE_ACK: u8 = 2;
+    pub const CONFIGURE_NAK: u8 = 3;
+    pub const CONFIGURE_REJECT: u8 = 4;
+    pub const TERMINATE_REQUEST: u8 = 5;
+    pub const TERMINATE_ACK: u8 = 6;
+    pub const CODE_REJECT: u8 = 7;
+    pub const PROTOCOL_REJECT: u8 = 8;
+    pub const ECHO_REQUEST: u8 = 9;
+    pub const ECHO_REPLY: u8 = 10;
+    pub const DISCARD_REQUEST: u8 = 11;
+}
+
+pub mod pap_codes {
+    pub const AUTHENTICATE_REQUEST: u8 = 1;
+    pub const AUTHENTICATE_ACK: u8 = 2;
+    pub const AUTHENTICATE_NAK: u8 = 3;
+}
+
+pub mod chap_codes {
+    pub const CHALLENGE: u8 = 1;
+    pub const RESPONSE: u8 = 2;
+    pub const SUCCESS: u8 = 3;
+    pub const FAILURE: u8 = 4;
+}
+
+/// Zero-copy view of a PPP header (the protocol field, as the address and control are
+/// never sent over PPPoE)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PppPacket<'a> {
+    bytes: &'a [u8],
+    header_length: usize,
+}
+
+impl<'a> PppPacket<'a> {
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        // a compressed protocol field is 1 byte, which is odd (2 bytes protocols have an even first byte)
+        let header_length = match bytes.first()? & 1 {
+            1 => 1,
+            _ => 2,
+        };
+        bytes.get(..header_length)?;
+        Some(Self {
+            bytes,
+            header_length,
+        })
+    }
+
+    pub fn get_protocol(&self) -> u16 {
+        match self.header_length {
+            1 => self.bytes[0].into(),
+            _ => u16::from_be_bytes([self.bytes[0], self.bytes[1]]),
+        }
+    }
+}
+
+impl Packet for PppPacket<'_> {
+    fn packet(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.bytes[self.header_length..]
+    }
+}
+
+/// Typed PPP control protocols messages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PppControl<'a> {
+    Lcp(PppControlPacket<'a>),
+    Ipcp(PppControlPacket<'a>),
+    Ipv6cp(PppControlPacket<'a>),
+    Pap(PapPacket<'a>),
+    Chap(ChapPacket<'a>),
+}
+
+impl<'a> PppControl<'a> {
+    /// Parse the payload of a PPP packet, `None` if `protocol` isn't a control protocol
+    /// or the message is too short
+    pub fn new(protocol: u16, bytes: &'a [u8]) -> Option<Self> {
+        Some(match protocol {
+            protocols::LCP => Self::Lcp(PppControlPacket::new(bytes)?),
+            protocols::IPCP => Self::Ipcp(PppControlPacket::new(bytes)?),
+            protocols::IPV6CP => Self::Ipv6cp(PppControlPacket::new(bytes)?),
+            protocols::PAP => Self::Pap(PapPacket::new(bytes)?),
+            protocols::CHAP => Self::Chap(ChapPacket::new(bytes)?),
+            _ => None?,
+        })
+    }
+
+    pub fn is_control_protocol(protocol: u16) -> bool {
+        matches!(
+            protocol,
+            protocols::LCP | protocols::IPCP | protocols::IPV6CP | protocols::PAP | protocols::CHAP
+        )
+    }
+}
+
+/// Bounds a control message to its length field, which includes the code, identifier and length
+fn control_bytes(bytes: &[u8]) -> Option<&[u8]> {
+    let header = bytes.get(..CONTROL_HEADER_LENGTH)?;
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+    if length < CONTROL_HEADER_LENGTH {
+        return None;
+    }
+    // captures may be cut before the declared length
+    Some(bytes.get(..length).unwrap_or(bytes))
+}
+
+/// All the control protocols share the code, identifier and length header
+macro_rules! impl_control_header {
+    ($($name:ident),*) => {
+        $(
+            impl<'a> $name<'a> {
+                pub fn new(bytes: &'a [u8]) -> Option<Self> {
+                    Some(Self {
+                        bytes: control_bytes(bytes)?,
+                    })
+                }
+
+                pub fn get_code(&self) -> u8 {
+                    self.bytes[0]
+                }
+
+                pub fn get_identifier(&self) -> u8 {
+                    self.bytes[1]
+                }
+
+                pub fn get_length(&self) -> u16 {
+                    u16::from_be_bytes([self.bytes[2], self.bytes[3]])
+                }
+            }
+
+            impl Packet for $name<'_> {
+                fn packet(&self) -> &[u8] {
+                    self.bytes
+                }
+
+                fn payload(&self) -> &[u8] {
+                    &self.bytes[CONTROL_HEADER_LENGTH..]
+                }
+            }
+        )*
+    };
+}
+
+impl_control_header!(PppControlPacket, PapPacket, ChapPacket);
+
+/// Zero-copy view of an LCP (RFC 1661) or NCP message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PppControlPacket<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PppControlPacket<'a> {
+    /// Configuration options, only configure messages have them
+    pub fn get_options(&self) -> Option<PppOptionsIterator<'a>> {
+        (control_codes::CONFIGURE_REQUEST..=control_codes::CONFIGURE_REJECT)
+            .contains(&self.get_code())
+            .then(|| PppOptionsIterator {
+                bytes: &self.bytes[CONTROL_HEADER_LENGTH..],
+            })
+    }
+
+    /// Magic number of echo and discard messages
+    pub fn get_magic_number(&self) -> Option<u32> {
+        if !(control_codes::ECHO_REQUEST..=control_codes::DISCARD_REQUEST)
+            .contains(&self.get_code())
+        {
+            return None;
+        }
+        let magic = self
+            .bytes
+            .get(CONTROL_HEADER_LENGTH..CONTROL_HEADER_LENGTH + 4)?;
+        Some(u32::from_be_bytes(magic.try_into().unwrap()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PppOption<'a> {
+    pub option_type: u8,
+    pub value: &'a [u8],
+}
+
+#[derive(Debug, Clone)]
+pub struct PppOptionsIterator<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for PppOptionsIterator<'a> {
+    type Item = PppOption<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let option_type = *self.bytes.first()?;
+        // the option length includes its type and length
+        let length = *self.bytes.get(1)? as usize;
+        let value = self.bytes.get(OPTION_HEADER_LENGTH..length)?;
+        self.bytes = &self.bytes[length..];
+        Some(PppOption { option_type, value })
+    }
+}
+
+/// Zero-copy view of a PAP message (RFC 1334)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PapPacket<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PapPacket<'a> {
+    pub fn get_peer_id(&self) -> Option<&'a [u8]> {
+        match self.get_code() {
+            pap_codes::AUTHENTICATE_REQUEST => length_prefixed(self.bytes, CONTROL_HEADER_LENGTH),
+            _ => None,
+        }
+    }
+
+    pub fn get_password(&self) -> Option<&'a [u8]> {
+        let peer_id = self.get_peer_id()?;
+        length_prefixed(self.bytes, CONTROL_HEADER_LENGTH + 1 + peer_id.len())
+    }
+
+    /// Message of ack and nak messages
+    pub fn get_message(&self) -> Option<&'a [u8]> {
+        match self.get_code() {
+            pap_codes::AUTHENTICATE_ACK | pap_codes::AUTHENTICATE_NAK => {
+                length_prefixed(self.bytes, CONTROL_HEADER_LENGTH)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Zero-copy view of a CHAP message (RFC 1994)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChapPacket<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ChapPacket<'a> {
+    /// Challenge or response value
+    pub fn get_value(&self) -> Option<&'a [u8]> {
+        match self.get_code() {
+            chap_codes::CHALLENGE | chap_codes::RESPONSE => {
+                length_prefixed(self.bytes, CONTROL_HEADER_LENGTH)
+            }
+            _ => None,
+        }
+    }
+
+    /// Name of the system that sent the challenge or response
+    pub fn get_name(&self) -> Option<&'a [u8]> {
+        let value = self.get_value()?;
+        self.bytes.get(CONTROL_HEADER_LENGTH + 1 + value.len()..)
+    }
+
+    /// Message of success and failure messages
+    pub fn get_message(&self) -> Option<&'a [u8]> {
+        match self.get_code() {
+            chap_codes::SUCCESS | chap_codes::FAILURE => self.bytes.get(CONTROL_HEADER_LENGTH..),
+            _ => None,
+        }
+    }
+}
+
+/// A field that starts with a 1 byte length
+fn length_prefixed(bytes: &[u8], offset: usize) -> Option<&[u8]> {
+    let length = *bytes.get(offset)? as usize;
+    bytes.get(offset + 1..offset + 1 + length)
+}
@@ -0,0 +1,124 @@
+use pnet::packet::Packet;
+
+pub const PPPOE_HEADER_LENGTH: usize = 6;
+const TAG_HEADER_LENGTH: usize = 4;
+
+/// Codes of the PPPoE header
+pub mod codes {
+    pub const SESSION: u8 = 0x00;
+    pub const PADO: u8 = 0x07;
+    pub const PADI: u8 = 0x09;
+    pub const PADR: u8 = 0x19;
+    pub const PADS: u8 = 0x65;
+    pub const PADT: u8 = 0xa7;
+}
+
+/// Types of the PPPoE discovery tags
+pub mod tag_types {
+    pub const END_OF_LIST: u16 = 0x0000;
+    pub const SERVICE_NAME: u16 = 0x0101;
+    pub const AC_NAME: u16 = 0x0102;
+    pub const HOST_UNIQ: u16 = 0x0103;
+    pub const AC_COOKIE: u16 = 0x0104;
+    pub const VENDOR_SPECIFIC: u16 = 0x0105;
+    pub const RELAY_SESSION_ID: u16 = 0x0110;
+    pub const SERVICE_NAME_ERROR: u16 = 0x0201;
+    pub const AC_SYSTEM_ERROR: u16 = 0x0202;
+    pub const GENERIC_ERROR: u16 = 0x0203;
+}
+
+/// Zero-copy view of a PPPoE header (RFC 2516), bounded by its length field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PppoePacket<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PppoePacket<'a> {
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        let header = bytes.get(..PPPOE_HEADER_LENGTH)?;
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        // captures may be cut before the declared length
+        let bytes = bytes.get(..PPPOE_HEADER_LENGTH + length).unwrap_or(bytes);
+        Some(Self { bytes })
+    }
+
+    pub fn get_version(&self) -> u8 {
+        self.bytes[0] >> 4
+    }
+
+    pub fn get_type(&self) -> u8 {
+        self.bytes[0] & 0xf
+    }
+
+    pub fn get_code(&self) -> u8 {
+        self.bytes[1]
+    }
+
+    pub fn get_session_id(&self) -> u16 {
+        u16::from_be_bytes([self.bytes[2], self.bytes[3]])
+    }
+
+    /// Length of the payload, as declared in the header
+    pub fn get_length(&self) -> u16 {
+        u16::from_be_bytes([self.bytes[4], self.bytes[5]])
+    }
+
+    pub fn is_discovery(&self) -> bool {
+        self.get_code() != codes::SESSION
+    }
+
+    /// Tags of discovery packets (session packets carry PPP instead)
+    pub fn get_tags(&self) -> PppoeTagsIterator<'a> {
+        PppoeTagsIterator {
+            bytes: &self.bytes[PPPOE_HEADER_LENGTH..],
+        }
+    }
+}
+
+impl Packet for PppoePacket<'_> {
+    fn packet(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.bytes[PPPOE_HEADER_LENGTH..]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PppoeTag<'a> {
+    pub tag_type: u16,
+    pub value: &'a [u8],
+}
+
+#[derive(Debug, Clone)]
+pub struct PppoeTagsIterator<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PppoeTagsIterator<'a> {
+    /// Value of the first tag of the given type
+    pub fn get(mut self, tag_type: u16) -> Option<&'a [u8]> {
+        self.find(|tag| tag.tag_type == tag_type)
+            .map(|tag| tag.value)
+    }
+}
+
+impl<'a> Iterator for PppoeTagsIterator<'a> {
+    type Item = PppoeTag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.bytes.get(..TAG_HEADER_LENGTH)?;
+        let tag_type = u16::from_be_bytes([header[0], header[1]]);
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let value = self
+            .bytes
+            .get(TAG_HEADER_LENGTH..TAG_HEADER_LENGTH + length)?;
+        self.bytes = match tag_type {
+            // there are no tags after the end of list (the rest may be padding)
+            tag_types::END_OF_LIST => &[],
+            _ => &self.bytes[TAG_HEADER_LENGTH + length..],
+        };
+        Some(PppoeTag { tag_type, value })
+    }
+}
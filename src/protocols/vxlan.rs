@@ -0,0 +1,42 @@
+use pnet::packet::Packet;
+
+pub const VXLAN_PORT: u16 = 4789;
+pub const VXLAN_HEADER_LENGTH: usize = 8;
+/// The VNI is only valid when this flag is set
+const FLAG_VNI: u8 = 0x08;
+
+/// Zero-copy view of a VXLAN header (RFC 7348)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VxlanPacket<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> VxlanPacket<'a> {
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        bytes.get(..VXLAN_HEADER_LENGTH)?;
+        Some(Self { bytes })
+    }
+
+    pub fn get_flags(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    pub fn is_vni_valid(&self) -> bool {
+        self.get_flags() & FLAG_VNI != 0
+    }
+
+    /// 24 bits VXLAN network identifier
+    pub fn get_vni(&self) -> u32 {
+        u32::from_be_bytes([0, self.bytes[4], self.bytes[5], self.bytes[6]])
+    }
+}
+
+impl Packet for VxlanPacket<'_> {
+    fn packet(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.bytes[VXLAN_HEADER_LENGTH..]
+    }
+}
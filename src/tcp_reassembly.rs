@@ -0,0 +1,318 @@
+use crate::{
+    l4::L4Packet,
+    packet::{HeaderPosition, Packet},
+    tuples::{Direction, FiveTuple},
+};
+use pnet::packet::{tcp::TcpFlags, Packet as _};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::Duration,
+};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TcpReassemblyError {
+    #[error("Packet is not a TCP segment")]
+    NotTcp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEventKind {
+    /// Ordered bytes of the stream
+    Data(Vec<u8>),
+    /// Amount of bytes missing from the stream
+    /// (never captured, or skipped because the buffer limit was reached)
+    Gap(u64),
+    /// The sender closed its side of the stream, after all of its data was delivered
+    Fin,
+    /// The sender reset the connection, and the flow was removed
+    /// (after its buffered data was delivered)
+    Reset,
+    /// No segments were seen for the idle timeout, and the flow was removed
+    /// (after its buffered data was delivered). Reported once, in the forward direction
+    Expired,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamEvent {
+    /// Tuple of the flow, as sent by the side that opened it
+    pub flow: FiveTuple,
+    /// Which side of the flow sent the stream
+    pub direction: Direction,
+    pub kind: StreamEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpReassemblyConfig {
+    /// Maximum amount of out of order bytes buffered per flow.
+    /// When it is reached, the missing bytes are reported as a gap and the buffered bytes are delivered
+    pub max_buffered_bytes: usize,
+    /// Flows without segments for this long are removed by `expire`
+    /// (connections that were abandoned, or whose teardown wasn't captured)
+    pub idle_timeout: Duration,
+}
+
+impl Default for TcpReassemblyConfig {
+    fn default() -> Self {
+        Self {
+            max_buffered_bytes: 1024 * 1024,
+            idle_timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+/// One direction of a TCP connection
+#[derive(Debug, Default)]
+struct HalfStream {
+    /// Sequence number of the first byte of the stream (ISN + 1)
+    base_sequence: Option<u32>,
+    /// Stream offset of the next byte to deliver
+    delivered: u64,
+    /// Out of order segments, by their stream offset
+    pending: BTreeMap<u64, Vec<u8>>,
+    /// Stream offset of the FIN
+    fin: Option<u64>,
+    closed: bool,
+}
+
+impl HalfStream {
+    /// Converts a sequence number to a stream offset.
+    /// Sequence numbers wrap around, so the offset closest to the delivered data is picked
+    fn offset_of(&self, sequence: u32) -> Option<u64> {
+        let next_sequence = self.base_sequence?.wrapping_add(self.delivered as u32);
+        let delta = sequence.wrapping_sub(next_sequence) as i32;
+        self.delivered.checked_add_signed(delta.into())
+    }
+
+    /// Buffers a segment, and returns the amount of bytes added to the buffer.
+    /// Data that was already delivered (retransmits) is dropped
+    fn insert(&mut self, start: u64, data: &[u8]) -> usize {
+        let skip = self.delivered.saturating_sub(start) as usize;
+        let Some(data) = data.get(skip..).filter(|data| !data.is_empty()) else {
+            return 0;
+        };
+        let start = start + skip as u64;
+        match self.pending.get(&start) {
+            Some(existing) if existing.len() >= data.len() => 0,
+            _ => {
+                let replaced = self.pending.insert(start, data.to_vec());
+                data.len() - replaced.map_or(0, |replaced| replaced.len())
+            }
+        }
+    }
+
+    /// Delivers the buffered data that continues the stream, and returns the amount of bytes
+    /// removed from the buffer. Overlapping data is delivered once, from the earliest segment
+    fn deliver(&mut self, mut emit: impl FnMut(StreamEventKind)) -> usize {
+        let mut removed = 0;
+        while let Some(entry) = self.pending.first_entry() {
+            let start = *entry.key();
+            if start > self.delivered {
+                break;
+            }
+            let mut data = entry.remove();
+            removed += data.len();
+            let skip = (self.delivered - start) as usize;
+            if skip < data.len() {
+                data.drain(..skip);
+                self.delivered += data.len() as u64;
+                emit(StreamEventKind::Data(data));
+            }
+        }
+        if !self.closed && self.fin.is_some_and(|fin| fin <= self.delivered) {
+            self.closed = true;
+            emit(StreamEventKind::Fin);
+        }
+        removed
+    }
+
+    /// Skips the missing bytes before the first buffered segment
+    fn skip_gap(&mut self, mut emit: impl FnMut(StreamEventKind)) {
+        if let Some(start) = self.pending.keys().next().copied() {
+            emit(StreamEventKind::Gap(start - self.delivered));
+            self.delivered = start;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Flow {
+    streams: [HalfStream; 2],
+    buffered: usize,
+    last_seen: Duration,
+}
+
+fn stream_index(direction: Direction) -> usize {
+    match direction {
+        Direction::Forward => 0,
+        Direction::Reverse => 1,
+    }
+}
+
+/// Reassembles TCP segments to ordered byte streams, per flow and direction.
+///
+/// Segments are added with `process`, and the ordered data (along with gaps and the connection
+/// lifecycle) is pulled with `next_event`.
+#[derive(Debug, Default)]
+pub struct TcpReassembler {
+    config: TcpReassemblyConfig,
+    flows: HashMap<FiveTuple, Flow>,
+    events: VecDeque<StreamEvent>,
+}
+
+impl TcpReassembler {
+    pub fn new(config: TcpReassemblyConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Adds the innermost TCP segment of a packet, that was captured at `timestamp`
+    pub fn process(
+        &mut self,
+        packet: &Packet,
+        timestamp: Duration,
+    ) -> Result<(), TcpReassemblyError> {
+        let Some(L4Packet::Tcp(tcp)) = packet.get_l4(HeaderPosition::Innermost) else {
+            return Err(TcpReassemblyError::NotTcp);
+        };
+        let tuple = packet
+            .get_five_tuple(HeaderPosition::Innermost)
+            .ok_or(TcpReassemblyError::NotTcp)?;
+        let flags = tcp.get_flags();
+        let (flow_tuple, direction) = self.orient(tuple, flags);
+
+        if flags & TcpFlags::RST != 0 {
+            if let Some(flow) = self.flows.remove(&flow_tuple) {
+                self.flush_flow(&flow_tuple, flow);
+                self.emit(&flow_tuple, direction, StreamEventKind::Reset);
+            }
+            return Ok(());
+        }
+
+        let payload = tcp.payload();
+        // segments without a SYN or data (like the last ACK of the teardown) don't open flows
+        if !self.flows.contains_key(&flow_tuple) && flags & TcpFlags::SYN == 0 && payload.is_empty()
+        {
+            return Ok(());
+        }
+        let flow = self.flows.entry(flow_tuple.clone()).or_default();
+        flow.last_seen = timestamp;
+        let stream = &mut flow.streams[stream_index(direction)];
+        let mut sequence = tcp.get_sequence();
+        if flags & TcpFlags::SYN != 0 {
+            // the SYN takes one sequence number
+            sequence = sequence.wrapping_add(1);
+        }
+        // streams that were picked up in the middle start at the first segment seen
+        stream.base_sequence.get_or_insert(sequence);
+        let Some(start) = stream.offset_of(sequence) else {
+            // data from before the start of the stream
+            return Ok(());
+        };
+        if flags & TcpFlags::FIN != 0 {
+            stream.fin = Some(start + payload.len() as u64);
+        }
+        flow.buffered += stream.insert(start, payload);
+
+        let mut events = Vec::new();
+        let mut emit = |kind| events.push((direction, kind));
+        flow.buffered -= stream.deliver(&mut emit);
+        while flow.buffered > self.config.max_buffered_bytes {
+            // the buffered data can't wait anymore, so the data before it is declared missing
+            let Some(direction) = [direction, direction.opposite()]
+                .into_iter()
+                .find(|direction| !flow.streams[stream_index(*direction)].pending.is_empty())
+            else {
+                break;
+            };
+            let mut emit = |kind| events.push((direction, kind));
+            let stream = &mut flow.streams[stream_index(direction)];
+            stream.skip_gap(&mut emit);
+            flow.buffered -= stream.deliver(&mut emit);
+        }
+
+        if flow.streams.iter().all(|stream| stream.closed) {
+            self.flows.remove(&flow_tuple);
+        }
+        for (direction, kind) in events {
+            self.emit(&flow_tuple, direction, kind);
+        }
+        Ok(())
+    }
+
+    /// Removes the flows that reached the idle timeout (after delivering their buffered data),
+    /// and returns their amount
+    pub fn expire(&mut self, now: Duration) -> usize {
+        let timeout = self.config.idle_timeout;
+        let expired: Vec<_> = self
+            .flows
+            .iter()
+            .filter(|(_, flow)| now.saturating_sub(flow.last_seen) >= timeout)
+            .map(|(flow_tuple, _)| flow_tuple.clone())
+            .collect();
+        for flow_tuple in &expired {
+            if let Some(flow) = self.flows.remove(flow_tuple) {
+                self.flush_flow(flow_tuple, flow);
+                self.emit(flow_tuple, Direction::Forward, StreamEventKind::Expired);
+            }
+        }
+        expired.len()
+    }
+
+    /// Delivers all the buffered data (reporting the gaps before it) and removes all the flows.
+    /// Should be called when there are no more packets, like at the end of a capture
+    pub fn flush(&mut self) {
+        for (flow_tuple, flow) in std::mem::take(&mut self.flows) {
+            self.flush_flow(&flow_tuple, flow);
+        }
+    }
+
+    /// Pulls the next stream event
+    pub fn next_event(&mut self) -> Option<StreamEvent> {
+        self.events.pop_front()
+    }
+
+    /// Amount of flows that are currently tracked
+    pub fn flow_count(&self) -> usize {
+        self.flows.len()
+    }
+
+    /// Finds the flow of a segment, and its direction in the flow.
+    /// New flows are opened by the sender of the segment, unless it is a SYN-ACK
+    fn orient(&self, tuple: FiveTuple, flags: u8) -> (FiveTuple, Direction) {
+        if self.flows.contains_key(&tuple) {
+            return (tuple, Direction::Forward);
+        }
+        let reversed = tuple.reversed();
+        let syn_ack = TcpFlags::SYN | TcpFlags::ACK;
+        if self.flows.contains_key(&reversed) || flags & syn_ack == syn_ack {
+            (reversed, Direction::Reverse)
+        } else {
+            (tuple, Direction::Forward)
+        }
+    }
+
+    /// Delivers the buffered data of a flow that was removed, reporting the gaps before it
+    fn flush_flow(&mut self, flow_tuple: &FiveTuple, mut flow: Flow) {
+        for direction in [Direction::Forward, Direction::Reverse] {
+            let stream = &mut flow.streams[stream_index(direction)];
+            let mut events = Vec::new();
+            while !stream.pending.is_empty() {
+                stream.skip_gap(|kind| events.push(kind));
+                stream.deliver(|kind| events.push(kind));
+            }
+            for kind in events {
+                self.emit(flow_tuple, direction, kind);
+            }
+        }
+    }
+
+    fn emit(&mut self, flow: &FiveTuple, direction: Direction, kind: StreamEventKind) {
+        self.events.push_back(StreamEvent {
+            flow: flow.clone(),
+            direction,
+            kind,
+        });
+    }
+}
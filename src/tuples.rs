@@ -2,7 +2,11 @@ use std::{fmt::Display, net::IpAddr};
 
 use pnet::packet::ip::IpNextHeaderProtocol;
 
-use crate::packet::{HeaderPosition, Packet};
+use crate::{
+    l3::L3Packet,
+    l4::L4Packet,
+    packet::{HeaderPosition, Packet},
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FourTuple {
@@ -22,20 +26,28 @@ impl Display for FourTuple {
     }
 }
 
+fn transport_headers<'b, 'a>(l3: &'b L3Packet<'a>) -> Option<(&'b L3Packet<'a>, &'b L4Packet<'a>)> {
+    Some((l3, l3.get_l4()?))
+}
+
 impl<'a> Packet<'a> {
-    pub fn get_four_tuple(&self, position: HeaderPosition) -> Option<FourTuple> {
-        let (l3, l4) = match position {
-            HeaderPosition::Inner | HeaderPosition::Outer => {
-                (self.get_l3(position)?, self.get_l4(position)?)
-            }
+    /// The L3 and L4 of a level, the innermost position is the innermost level that has both
+    fn get_transport_headers(
+        &self,
+        position: HeaderPosition,
+    ) -> Option<(&L3Packet<'a>, &L4Packet<'a>)> {
+        match position {
             HeaderPosition::Innermost => self
-                .get_l3(HeaderPosition::Inner)
-                .and_then(|l3| self.get_l4(HeaderPosition::Inner).map(|l4| (l3, l4)))
-                .or_else(|| {
-                    self.get_l3(HeaderPosition::Outer)
-                        .and_then(|l3| self.get_l4(HeaderPosition::Outer).map(|l4| (l3, l4)))
-                })?,
-        };
+                .get_levels()
+                .iter()
+                .rev()
+                .find_map(|level| transport_headers(level.get_l3()?)),
+            position => transport_headers(self.get_l3(position)?),
+        }
+    }
+
+    pub fn get_four_tuple(&self, position: HeaderPosition) -> Option<FourTuple> {
+        let (l3, l4) = self.get_transport_headers(position)?;
 
         Some(FourTuple {
             source_ip: l3.get_source()?,
@@ -71,18 +83,7 @@ impl Display for FiveTuple {
 
 impl<'a> Packet<'a> {
     pub fn get_five_tuple(&self, position: HeaderPosition) -> Option<FiveTuple> {
-        let (l3, l4) = match position {
-            HeaderPosition::Inner | HeaderPosition::Outer => {
-                (self.get_l3(position)?, self.get_l4(position)?)
-            }
-            HeaderPosition::Innermost => self
-                .get_l3(HeaderPosition::Inner)
-                .and_then(|l3| self.get_l4(HeaderPosition::Inner).map(|l4| (l3, l4)))
-                .or_else(|| {
-                    self.get_l3(HeaderPosition::Outer)
-                        .and_then(|l3| self.get_l4(HeaderPosition::Outer).map(|l4| (l3, l4)))
-                })?,
-        };
+        let (l3, l4) = self.get_transport_headers(position)?;
 
         Some(FiveTuple {
             source_ip: l3.get_source()?,
@@ -94,6 +95,48 @@ impl<'a> Packet<'a> {
     }
 }
 
+impl FiveTuple {
+    /// The tuple of the packets going the other way in the same flow
+    pub fn reversed(&self) -> Self {
+        Self {
+            source_ip: self.destination_ip,
+            source_port: self.destination_port,
+            destination_ip: self.source_ip,
+            destination_port: self.source_port,
+            protocol: self.protocol,
+        }
+    }
+
+    /// Direction independent form of the tuple, that is the same for both directions of a flow
+    /// (the lower ip and port are the source). Also returns the direction of this tuple
+    /// relative to the canonical one
+    pub fn canonical(&self) -> (Self, Direction) {
+        if (self.source_ip, self.source_port) <= (self.destination_ip, self.destination_port) {
+            (self.clone(), Direction::Forward)
+        } else {
+            (self.reversed(), Direction::Reverse)
+        }
+    }
+}
+
+/// Direction of a packet in a bidirectional flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Same direction as the flow's tuple (usually from the side that opened the flow)
+    Forward,
+    /// The opposite direction
+    Reverse,
+}
+
+impl Direction {
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::Forward => Direction::Reverse,
+            Direction::Reverse => Direction::Forward,
+        }
+    }
+}
+
 impl From<FiveTuple> for FourTuple {
     fn from(five_tuple: FiveTuple) -> Self {
         Self {
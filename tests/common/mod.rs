@@ -0,0 +1,26 @@
+pub const CLIENT: [u8; 4] = [10, 0, 0, 1];
+pub const SERVER: [u8; 4] = [10, 0, 0, 2];
+
+/// Builds an ethernet frame of a TCP segment between `CLIENT:40000` and `SERVER:80`
+pub fn tcp_segment(from_client: bool, sequence: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let (source, destination, source_port, destination_port) = match from_client {
+        true => (CLIENT, SERVER, 40000u16, 80u16),
+        false => (SERVER, CLIENT, 80, 40000),
+    };
+    let total_length = (20 + 20 + payload.len()) as u16;
+    let mut packet = vec![
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+    ];
+    packet.extend_from_slice(&[0x45, 0x00]);
+    packet.extend_from_slice(&total_length.to_be_bytes());
+    packet.extend_from_slice(&[0x00, 0x01, 0x40, 0x00, 0x40, 0x06, 0x00, 0x00]);
+    packet.extend_from_slice(&source);
+    packet.extend_from_slice(&destination);
+    packet.extend_from_slice(&source_port.to_be_bytes());
+    packet.extend_from_slice(&destination_port.to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x50, flags, 0x20, 0x00]);
+    packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+    packet.extend_from_slice(payload);
+    packet
+}
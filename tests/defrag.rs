@@ -0,0 +1,337 @@
+use packet_parser::{
+    defrag::{DefragConfig, DefragError, Defragmenter, OverlapPolicy},
+    l3::L3Packet,
+    l4::L4Packet,
+    packet::{HeaderPosition, Packet},
+};
+use pnet::packet::ipv4;
+use std::time::Duration;
+
+const UDP_HEADER: [u8; 8] = [0x04, 0xd2, 0x00, 0x35, 0x00, 0x20, 0x00, 0x00];
+
+fn ipv4_fragment(identification: u16, offset: usize, more_fragments: bool, data: &[u8]) -> Vec<u8> {
+    let total_length = (20 + data.len()) as u16;
+    let flags_and_offset = (offset / 8) as u16 | if more_fragments { 0x2000 } else { 0 };
+    let mut packet = vec![
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+    ];
+    packet.extend_from_slice(&[0x45, 0x00]);
+    packet.extend_from_slice(&total_length.to_be_bytes());
+    packet.extend_from_slice(&identification.to_be_bytes());
+    packet.extend_from_slice(&flags_and_offset.to_be_bytes());
+    packet.extend_from_slice(&[
+        0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+    ]);
+    packet.extend_from_slice(data);
+    packet
+}
+
+fn ipv6_fragment(identification: u32, offset: usize, more_fragments: bool, data: &[u8]) -> Vec<u8> {
+    let payload_length = (8 + data.len()) as u16;
+    let offset_and_flags = offset as u16 | more_fragments as u16;
+    let mut packet = vec![
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x86, 0xdd,
+    ];
+    packet.extend_from_slice(&[0x60, 0x00, 0x00, 0x00]);
+    packet.extend_from_slice(&payload_length.to_be_bytes());
+    packet.extend_from_slice(&[0x2c, 0x40]);
+    packet.extend_from_slice(&[
+        0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01,
+    ]);
+    packet.extend_from_slice(&[
+        0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02,
+    ]);
+    packet.extend_from_slice(&[0x11, 0x00]);
+    packet.extend_from_slice(&offset_and_flags.to_be_bytes());
+    packet.extend_from_slice(&identification.to_be_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
+
+fn udp_datagram() -> Vec<u8> {
+    let mut datagram = UDP_HEADER.to_vec();
+    datagram.extend(1..=24);
+    datagram
+}
+
+fn process(defragmenter: &mut Defragmenter, frame: &[u8]) -> Result<Option<Vec<u8>>, DefragError> {
+    let packet = Packet::try_from(frame).expect("fragment parse failed");
+    defragmenter.process(&packet, HeaderPosition::Outer, Duration::ZERO)
+}
+
+#[test]
+fn test_ipv4_reassembly() {
+    let datagram = udp_datagram();
+    let fragments = [
+        ipv4_fragment(7, 0, true, &datagram[..16]),
+        ipv4_fragment(7, 16, true, &datagram[16..24]),
+        ipv4_fragment(7, 24, false, &datagram[24..]),
+    ];
+
+    for order in [[0, 1, 2], [2, 0, 1], [1, 2, 0]] {
+        let mut defragmenter = Defragmenter::new(DefragConfig::default());
+        assert_eq!(process(&mut defragmenter, &fragments[order[0]]), Ok(None));
+        assert_eq!(process(&mut defragmenter, &fragments[order[1]]), Ok(None));
+        let reassembled = process(&mut defragmenter, &fragments[order[2]])
+            .expect("reassembly failed")
+            .expect("packet should be complete");
+        assert_eq!(defragmenter.pending(), 0);
+        assert_eq!(defragmenter.memory_usage(), 0);
+
+        let parsed = Packet::try_from(reassembled.as_slice()).expect("packet parse failed");
+        let Some(L3Packet::Ipv4(ip, L4Packet::Udp(_))) = parsed.get_l3(HeaderPosition::Outer)
+        else {
+            panic!("Invalid packet type")
+        };
+        assert!(!parsed.get_l3(HeaderPosition::Outer).unwrap().is_fragment());
+        assert_eq!(ip.get_total_length(), 20 + datagram.len() as u16);
+        assert_eq!(ip.get_checksum(), ipv4::checksum(ip));
+        assert_eq!(parsed.get_payload(), Some(&datagram[8..]));
+    }
+}
+
+#[test]
+fn test_ipv6_reassembly() {
+    let datagram = udp_datagram();
+    let mut defragmenter = Defragmenter::new(DefragConfig::default());
+    let last = ipv6_fragment(0xabcd, 16, false, &datagram[16..]);
+    let first = ipv6_fragment(0xabcd, 0, true, &datagram[..16]);
+    assert_eq!(process(&mut defragmenter, &last), Ok(None));
+    let reassembled = process(&mut defragmenter, &first)
+        .expect("reassembly failed")
+        .expect("packet should be complete");
+
+    let parsed = Packet::try_from(reassembled.as_slice()).expect("packet parse failed");
+    let Some(L3Packet::Ipv6(ip, extensions, L4Packet::Udp(_))) =
+        parsed.get_l3(HeaderPosition::Outer)
+    else {
+        panic!("Invalid packet type")
+    };
+    assert!(extensions.extensions.is_empty());
+    assert_eq!(ip.get_payload_length(), datagram.len() as u16);
+    assert_eq!(parsed.get_payload(), Some(&datagram[8..]));
+}
+
+#[test]
+fn test_ipv6_reassembly_with_extensions() {
+    // destination options after the fragment header are only parsed in the first fragment
+    let mut data = vec![0x11, 0x00, 0x01, 0x04, 0x00, 0x00, 0x00, 0x00];
+    data.extend_from_slice(&udp_datagram());
+    let mut fragments = [
+        ipv6_fragment(0xabcd, 0, true, &data[..16]),
+        ipv6_fragment(0xabcd, 16, false, &data[16..]),
+    ];
+    for fragment in &mut fragments {
+        fragment[54] = 0x3c;
+    }
+    let mut defragmenter = Defragmenter::new(DefragConfig::default());
+    assert_eq!(process(&mut defragmenter, &fragments[0]), Ok(None));
+    let reassembled = process(&mut defragmenter, &fragments[1])
+        .expect("reassembly failed")
+        .expect("packet should be complete");
+
+    let parsed = Packet::try_from(reassembled.as_slice()).expect("packet parse failed");
+    let Some(L3Packet::Ipv6(_, extensions, L4Packet::Udp(_))) =
+        parsed.get_l3(HeaderPosition::Outer)
+    else {
+        panic!("Invalid packet type")
+    };
+    assert_eq!(extensions.extensions.len(), 1);
+    assert_eq!(parsed.get_payload(), Some(&data[16..]));
+}
+
+#[test]
+fn test_overlap_policies() {
+    // the second fragment is overlapped by the third one, which starts at the same offset
+    let same_offset = [
+        ipv4_fragment(1, 0, true, &UDP_HEADER),
+        ipv4_fragment(1, 8, true, &[0xaa; 8]),
+        ipv4_fragment(1, 8, false, &[0xbb; 16]),
+    ];
+    // the second fragment is overlapped by the third one, which starts before it
+    let earlier_offset = [
+        ipv4_fragment(2, 0, true, &UDP_HEADER),
+        ipv4_fragment(2, 16, true, &[0xaa; 8]),
+        ipv4_fragment(2, 8, false, &[0xbb; 16]),
+    ];
+
+    let cases = [
+        (OverlapPolicy::First, 0xaa, 0xaa),
+        (OverlapPolicy::Last, 0xbb, 0xbb),
+        (OverlapPolicy::Bsd, 0xaa, 0xbb),
+        (OverlapPolicy::Linux, 0xbb, 0xbb),
+    ];
+    for (overlap_policy, same_offset_winner, earlier_offset_winner) in cases {
+        let config = DefragConfig {
+            overlap_policy,
+            ..Default::default()
+        };
+        for (fragments, winner, overlap) in [
+            (&same_offset, same_offset_winner, 8..16),
+            (&earlier_offset, earlier_offset_winner, 16..24),
+        ] {
+            let mut defragmenter = Defragmenter::new(config.clone());
+            assert_eq!(process(&mut defragmenter, &fragments[0]), Ok(None));
+            assert_eq!(process(&mut defragmenter, &fragments[1]), Ok(None));
+            let reassembled = process(&mut defragmenter, &fragments[2])
+                .expect("reassembly failed")
+                .expect("packet should be complete");
+            let payload = &reassembled[34..];
+            assert_eq!(payload.len(), 24);
+            assert!(
+                payload[overlap].iter().all(|byte| *byte == winner),
+                "wrong data kept with {overlap_policy:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_defrag_timeout() {
+    let datagram = udp_datagram();
+    let first = ipv4_fragment(3, 0, true, &datagram[..16]);
+    let last = ipv4_fragment(3, 16, false, &datagram[16..]);
+    let mut defragmenter = Defragmenter::new(DefragConfig {
+        timeout: Duration::from_secs(30),
+        ..Default::default()
+    });
+
+    let packet = Packet::try_from(first.as_slice()).expect("fragment parse failed");
+    assert_eq!(
+        defragmenter.process(&packet, HeaderPosition::Outer, Duration::from_secs(100)),
+        Ok(None)
+    );
+    assert_eq!(defragmenter.expire(Duration::from_secs(110)), 0);
+    assert_eq!(defragmenter.pending(), 1);
+    assert_eq!(defragmenter.expire(Duration::from_secs(130)), 1);
+    assert_eq!(defragmenter.pending(), 0);
+    assert_eq!(defragmenter.memory_usage(), 0);
+
+    let packet = Packet::try_from(last.as_slice()).expect("fragment parse failed");
+    assert_eq!(
+        defragmenter.process(&packet, HeaderPosition::Outer, Duration::from_secs(131)),
+        Ok(None)
+    );
+}
+
+#[test]
+fn test_defrag_memory_limit() {
+    let mut defragmenter = Defragmenter::new(DefragConfig {
+        max_memory: 16,
+        ..Default::default()
+    });
+    assert_eq!(
+        process(&mut defragmenter, &ipv4_fragment(4, 0, true, &[0; 24])),
+        Err(DefragError::MemoryLimit)
+    );
+
+    assert_eq!(
+        process(&mut defragmenter, &ipv4_fragment(5, 0, true, &[0; 16])),
+        Ok(None)
+    );
+    assert_eq!(defragmenter.memory_usage(), 16);
+    // the oldest incomplete packet is dropped to make room for the new one
+    assert_eq!(
+        process(&mut defragmenter, &ipv4_fragment(6, 0, true, &[0; 8])),
+        Ok(None)
+    );
+    assert_eq!(defragmenter.pending(), 1);
+    assert_eq!(defragmenter.memory_usage(), 8);
+
+    // the packet that is being filled isn't dropped to make room for its own fragment
+    assert_eq!(
+        process(&mut defragmenter, &ipv4_fragment(6, 8, true, &[0; 16])),
+        Err(DefragError::MemoryLimit)
+    );
+    assert_eq!(defragmenter.pending(), 1);
+    assert_eq!(defragmenter.memory_usage(), 8);
+}
+
+#[test]
+fn test_defrag_errors() {
+    let mut defragmenter = Defragmenter::new(DefragConfig::default());
+    let mut not_fragment = ipv4_fragment(8, 0, false, &udp_datagram());
+    not_fragment[14 + 6] = 0x40;
+    assert_eq!(
+        process(&mut defragmenter, &not_fragment),
+        Err(DefragError::NotAFragment)
+    );
+
+    assert_eq!(
+        process(&mut defragmenter, &ipv4_fragment(9, 8, false, &[0; 8])),
+        Ok(None)
+    );
+    assert_eq!(
+        process(&mut defragmenter, &ipv4_fragment(9, 16, false, &[0; 8])),
+        Err(DefragError::InconsistentLength)
+    );
+    assert_eq!(defragmenter.pending(), 0);
+
+    // the IPv4 total length counts the 20 bytes header as well
+    assert_eq!(
+        process(&mut defragmenter, &ipv4_fragment(10, 65504, false, &[0; 8])),
+        Ok(None)
+    );
+    assert_eq!(
+        process(&mut defragmenter, &ipv4_fragment(11, 65512, false, &[0; 8])),
+        Err(DefragError::FragmentTooLarge)
+    );
+}
+
+#[test]
+fn test_defrag_without_l2() {
+    let datagram = udp_datagram();
+    let first = ipv4_fragment(12, 0, true, &datagram[..16]);
+    let last = ipv4_fragment(12, 16, false, &datagram[16..]);
+
+    // raw IP fragments are reassembled to a raw IP packet
+    let mut defragmenter = Defragmenter::new(DefragConfig::default());
+    for (fragment, expected_complete) in [(&first, false), (&last, true)] {
+        let packet = Packet::from_ip(&fragment[14..]).expect("fragment parse failed");
+        let reassembled = defragmenter
+            .process(&packet, HeaderPosition::Outer, Duration::ZERO)
+            .expect("reassembly failed");
+        assert_eq!(reassembled.is_some(), expected_complete);
+        if let Some(reassembled) = reassembled {
+            let parsed = Packet::from_ip(&reassembled).expect("packet parse failed");
+            assert_eq!(parsed.get_payload(), Some(&datagram[8..]));
+        }
+    }
+
+    // fragments inside an IP in IP tunnel
+    let mut defragmenter = Defragmenter::new(DefragConfig::default());
+    for (fragment, expected_complete) in [(&last, false), (&first, true)] {
+        let inner = &fragment[14..];
+        let mut tunneled = fragment[..14].to_vec();
+        tunneled.extend_from_slice(&[0x45, 0x00]);
+        tunneled.extend_from_slice(&(20 + inner.len() as u16).to_be_bytes());
+        tunneled.extend_from_slice(&[
+            0x00, 0x00, 0x00, 0x00, 0x40, 0x04, 0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8,
+            0x00, 0x02,
+        ]);
+        tunneled.extend_from_slice(inner);
+        let packet = Packet::try_from(tunneled.as_slice()).expect("fragment parse failed");
+        let reassembled = defragmenter
+            .process(&packet, HeaderPosition::Inner, Duration::ZERO)
+            .expect("reassembly failed");
+        assert_eq!(reassembled.is_some(), expected_complete);
+        if let Some(reassembled) = reassembled {
+            let parsed = Packet::from_ip(&reassembled).expect("packet parse failed");
+            assert_eq!(
+                parsed
+                    .get_l3(HeaderPosition::Outer)
+                    .and_then(|l3| l3.get_source()),
+                Some([10, 0, 0, 1].into())
+            );
+            assert_eq!(parsed.get_payload(), Some(&datagram[8..]));
+        }
+    }
+    assert_eq!(
+        defragmenter.process(
+            &Packet::try_from(first.as_slice()).expect("fragment parse failed"),
+            HeaderPosition::Inner,
+            Duration::ZERO
+        ),
+        Err(DefragError::NotAFragment)
+    );
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::{tcp_segment, CLIENT, SERVER};
+use packet_parser::{
+    flow::{ExpiryReason, FlowTable, FlowTableConfig},
+    packet::{HeaderPosition, Packet},
+    tuples::{Direction, FiveTuple},
+};
+use pnet::packet::{ip::IpNextHeaderProtocols, tcp::TcpFlags};
+use std::{net::IpAddr, time::Duration};
+
+fn client_tuple() -> FiveTuple {
+    FiveTuple {
+        source_ip: IpAddr::from(CLIENT),
+        source_port: 40000,
+        destination_ip: IpAddr::from(SERVER),
+        destination_port: 80,
+        protocol: IpNextHeaderProtocols::Tcp,
+    }
+}
+
+fn process(table: &mut FlowTable, frame: &[u8], seconds: u64) -> Option<Direction> {
+    let packet = Packet::try_from(frame).expect("packet parse failed");
+    table
+        .process(&packet, Duration::from_secs(seconds))
+        .map(|(_, direction)| direction)
+}
+
+#[test]
+fn test_flow_counters() {
+    let mut table = FlowTable::default();
+    let syn = tcp_segment(true, 1, TcpFlags::SYN, &[]);
+    let syn_ack = tcp_segment(false, 1, TcpFlags::SYN | TcpFlags::ACK, &[]);
+    let data = tcp_segment(true, 1, TcpFlags::ACK | TcpFlags::PSH, b"hello");
+    assert_eq!(process(&mut table, &syn, 1), Some(Direction::Forward));
+    assert_eq!(process(&mut table, &syn_ack, 2), Some(Direction::Reverse));
+    assert_eq!(process(&mut table, &data, 3), Some(Direction::Forward));
+    assert_eq!(table.len(), 1);
+
+    // the flow is found from both directions
+    let flow = table.get(&client_tuple().reversed()).expect("flow missing");
+    assert_eq!(flow, table.get(&client_tuple()).expect("flow missing"));
+    assert_eq!(flow.tuple, client_tuple());
+    assert_eq!(flow.position, HeaderPosition::Outer);
+    assert_eq!(flow.first_seen, Duration::from_secs(1));
+    assert_eq!(flow.last_seen, Duration::from_secs(3));
+    assert_eq!(flow.forward.packets, 2);
+    assert_eq!(flow.forward.bytes, 40 + 45);
+    assert_eq!(
+        flow.forward.tcp_flags,
+        TcpFlags::SYN | TcpFlags::ACK | TcpFlags::PSH
+    );
+    assert_eq!(flow.get_counters(Direction::Reverse).packets, 1);
+    assert_eq!(flow.reverse.bytes, 40);
+    assert_eq!(flow.reverse.tcp_flags, TcpFlags::SYN | TcpFlags::ACK);
+}
+
+#[test]
+fn test_flow_position() {
+    let mut table = FlowTable::new(FlowTableConfig {
+        position: HeaderPosition::Inner,
+        ..Default::default()
+    });
+    let syn = tcp_segment(true, 1, TcpFlags::SYN, &[]);
+    // the packet isn't tunneled, so there is no inner tuple
+    assert_eq!(process(&mut table, &syn, 0), None);
+    assert!(table.is_empty());
+
+    // the same level is recorded as `Inner`, however it was configured
+    let mut ip_in_ip = syn[..14].to_vec();
+    ip_in_ip.extend_from_slice(&[0x45, 0x00]);
+    ip_in_ip.extend_from_slice(&(syn.len() as u16 - 14 + 20).to_be_bytes());
+    ip_in_ip.extend_from_slice(&[0x00, 0x01, 0x40, 0x00, 0x40, 0x04, 0x00, 0x00]);
+    ip_in_ip.extend_from_slice(&[192, 168, 0, 1, 192, 168, 0, 2]);
+    ip_in_ip.extend_from_slice(&syn[14..]);
+    for position in [
+        HeaderPosition::Inner,
+        HeaderPosition::Innermost,
+        HeaderPosition::Nth(1),
+    ] {
+        let mut table = FlowTable::new(FlowTableConfig {
+            position,
+            ..Default::default()
+        });
+        assert_eq!(process(&mut table, &ip_in_ip, 0), Some(Direction::Forward));
+        let flow = table.get(&client_tuple()).expect("flow missing");
+        assert_eq!(flow.position, HeaderPosition::Inner);
+    }
+}
+
+#[test]
+fn test_flow_expiry() {
+    let mut table = FlowTable::new(FlowTableConfig {
+        idle_timeout: Duration::from_secs(10),
+        active_timeout: Duration::from_secs(60),
+        ..Default::default()
+    });
+    let request = tcp_segment(true, 1, TcpFlags::ACK, b"ping");
+    let response = tcp_segment(false, 1, TcpFlags::ACK, b"pong");
+    process(&mut table, &request, 0);
+    assert_eq!(table.expire(Duration::from_secs(9)).count(), 0);
+
+    // the flow stays active, until it reaches the active timeout
+    for seconds in (5..60).step_by(5) {
+        process(&mut table, &response, seconds);
+        assert_eq!(table.expire(Duration::from_secs(seconds)).count(), 0);
+    }
+    process(&mut table, &request, 61);
+    let expired: Vec<_> = table.expire(Duration::from_secs(61)).collect();
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].0.tuple, client_tuple());
+    assert_eq!(expired[0].0.forward.packets, 2);
+    assert_eq!(expired[0].0.reverse.packets, 11);
+    assert_eq!(expired[0].1, ExpiryReason::Active);
+    assert!(table.is_empty());
+
+    // a new flow starts from the response direction
+    process(&mut table, &response, 70);
+    let expired: Vec<_> = table.expire(Duration::from_secs(80)).collect();
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].0.tuple, client_tuple().reversed());
+    assert_eq!(expired[0].1, ExpiryReason::Idle);
+}
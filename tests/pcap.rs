@@ -0,0 +1,194 @@
+use packet_parser::{
+    capture::{
+        pcap::{ParseError, PcapHeader, PcapReader, PcapWriter, TimestampResolution},
+        LinkType,
+    },
+    packet::{self, HeaderPosition, Packet},
+};
+use std::time::Duration;
+
+const PACKET: [u8; 64] = [
+    0x78, 0x2b, 0x46, 0x4b, 0x3b, 0xab, 0xb4, 0x8c, 0x9d, 0x5d, 0x81, 0x8b, 0x08, 0x00, 0x45, 0x00,
+    0x00, 0x32, 0x36, 0x2b, 0x40, 0x00, 0x80, 0x06, 0x08, 0x94, 0xc0, 0xa8, 0x1d, 0x11, 0xc0, 0xa8,
+    0x1d, 0xa5, 0xec, 0x62, 0x63, 0xdd, 0xc6, 0xef, 0xa3, 0xdf, 0x88, 0xce, 0x7e, 0xbc, 0x50, 0x18,
+    0x02, 0x01, 0x0e, 0x83, 0x00, 0x00, 0x08, 0xff, 0x08, 0x00, 0x07, 0x9e, 0x08, 0x00, 0x00, 0x00,
+];
+
+fn pcap_file(
+    magic: u32,
+    big_endian: bool,
+    link_type: u32,
+    records: &[(u32, u32, &[u8])],
+) -> Vec<u8> {
+    let u16_bytes = |value: u16| match big_endian {
+        true => value.to_be_bytes(),
+        false => value.to_le_bytes(),
+    };
+    let u32_bytes = |value: u32| match big_endian {
+        true => value.to_be_bytes(),
+        false => value.to_le_bytes(),
+    };
+    let mut file = u32_bytes(magic).to_vec();
+    file.extend_from_slice(&u16_bytes(2));
+    file.extend_from_slice(&u16_bytes(4));
+    file.extend_from_slice(&[0; 8]);
+    file.extend_from_slice(&u32_bytes(65535));
+    file.extend_from_slice(&u32_bytes(link_type));
+    for (seconds, fraction, data) in records {
+        file.extend_from_slice(&u32_bytes(*seconds));
+        file.extend_from_slice(&u32_bytes(*fraction));
+        file.extend_from_slice(&u32_bytes(data.len() as u32));
+        file.extend_from_slice(&u32_bytes(data.len() as u32 + 4));
+        file.extend_from_slice(data);
+    }
+    file
+}
+
+#[test]
+fn test_pcap_byte_orders_and_resolutions() {
+    let cases = [
+        (0xa1b2c3d4, false, TimestampResolution::Microseconds, 1_000),
+        (0xa1b2c3d4, true, TimestampResolution::Microseconds, 1_000),
+        (0xa1b23c4d, false, TimestampResolution::Nanoseconds, 1),
+        (0xa1b23c4d, true, TimestampResolution::Nanoseconds, 1),
+    ];
+    for (magic, big_endian, resolution, nanoseconds_per_unit) in cases {
+        let file = pcap_file(
+            magic,
+            big_endian,
+            1,
+            &[(100, 5, &PACKET), (101, 7, &PACKET[..20])],
+        );
+        let reader = PcapReader::new(&file).expect("header parse failed");
+        assert_eq!(reader.get_header().big_endian, big_endian);
+        assert_eq!(reader.get_header().timestamp_resolution, resolution);
+        assert_eq!(reader.get_header().version_major, 2);
+        assert_eq!(reader.get_header().version_minor, 4);
+        assert_eq!(reader.get_header().snaplen, 65535);
+        assert_eq!(reader.get_link_type(), LinkType::ETHERNET);
+
+        let records: Vec<_> = reader
+            .collect::<Result<_, _>>()
+            .expect("records parse failed");
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].timestamp,
+            Duration::from_secs(100) + Duration::from_nanos(5 * nanoseconds_per_unit)
+        );
+        assert_eq!(records[0].captured_length, 64);
+        assert_eq!(records[0].original_length, 68);
+        assert_eq!(records[0].data, PACKET);
+        assert_eq!(records[1].data, &PACKET[..20]);
+    }
+}
+
+#[test]
+fn test_pcap_records_to_packets() {
+    let file = pcap_file(0xa1b2c3d4, false, 1, &[(0, 0, &PACKET)]);
+    let reader = PcapReader::new(&file).expect("header parse failed");
+    let link_type = reader.get_link_type();
+
+    let allocations = allocation_counter::measure(|| {
+        for record in reader.clone() {
+            let record = record.expect("record parse failed");
+            let packet =
+                Packet::from_link_type(link_type, record.data).expect("packet parse failed");
+            assert!(packet.get_five_tuple(HeaderPosition::Outer).is_some());
+        }
+    });
+    assert_eq!(allocations.count_total, 0);
+
+    let file = pcap_file(0xa1b2c3d4, false, 147, &[(0, 0, &PACKET)]);
+    let reader = PcapReader::new(&file).expect("header parse failed");
+    assert_eq!(reader.get_link_type(), LinkType(147));
+    let record = reader
+        .into_iter()
+        .next()
+        .unwrap()
+        .expect("record parse failed");
+    assert!(matches!(
+        Packet::from_link_type(LinkType(147), record.data),
+        Err(packet::ParseError::UnsupportedLinkType(LinkType(147)))
+    ));
+}
+
+#[test]
+fn test_pcap_errors() {
+    assert_eq!(
+        PcapReader::new(&[0xd4, 0xc3]).err(),
+        Some(ParseError::TruncatedHeader)
+    );
+    assert_eq!(
+        PcapReader::new(&[0; 24]).err(),
+        Some(ParseError::InvalidMagic(0))
+    );
+
+    let mut file = pcap_file(0xa1b2c3d4, false, 1, &[(0, 0, &PACKET), (0, 0, &PACKET)]);
+    file.truncate(file.len() - 1);
+    let mut reader = PcapReader::new(&file).expect("header parse failed");
+    assert!(reader.next().unwrap().is_ok());
+    assert_eq!(
+        reader.next(),
+        Some(Err(ParseError::TruncatedRecord(24 + 16 + 64)))
+    );
+    assert_eq!(reader.next(), None);
+}
+
+#[test]
+fn test_pcap_round_trip() {
+    for (magic, big_endian) in [(0xa1b2c3d4, false), (0xa1b23c4d, true)] {
+        let file = pcap_file(
+            magic,
+            big_endian,
+            1,
+            &[(100, 5, &PACKET), (101, 7, &PACKET[..20])],
+        );
+        let reader = PcapReader::new(&file).expect("header parse failed");
+        let mut writer =
+            PcapWriter::new(Vec::new(), reader.get_header().clone()).expect("write failed");
+        for record in reader {
+            writer
+                .write_record(&record.expect("record parse failed"))
+                .expect("write failed");
+        }
+        assert_eq!(writer.into_inner(), file);
+    }
+}
+
+#[test]
+fn test_pcap_writer() {
+    let header = PcapHeader {
+        big_endian: false,
+        timestamp_resolution: TimestampResolution::Nanoseconds,
+        version_major: 2,
+        version_minor: 4,
+        snaplen: 65535,
+        link_type: LinkType::ETHERNET,
+    };
+    let mut writer = PcapWriter::new(Vec::new(), header.clone()).expect("write failed");
+    let packet = Packet::try_from(PACKET.as_slice()).expect("packet parse failed");
+    writer
+        .write_packet(&packet, Duration::new(7, 42), 64)
+        .expect("write failed");
+    // the packet was snapped, so its original length is longer than its data
+    writer
+        .write_packet(&packet, Duration::new(8, 0), 1500)
+        .expect("write failed");
+    // the timestamp doesn't fit in 32 bits of seconds
+    assert!(writer
+        .write_packet(&packet, Duration::from_secs(1 << 32), 64)
+        .is_err());
+
+    let written = writer.into_inner();
+    let reader = PcapReader::new(&written).expect("header parse failed");
+    assert_eq!(reader.get_header(), &header);
+    let records: Vec<_> = reader
+        .collect::<Result<_, _>>()
+        .expect("records parse failed");
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].timestamp, Duration::new(7, 42));
+    assert_eq!(records[0].original_length, 64);
+    assert_eq!(records[0].data, PACKET);
+    assert_eq!(records[1].original_length, 1500);
+    assert_eq!(records[1].data, PACKET);
+}
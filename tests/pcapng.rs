@@ -0,0 +1,345 @@
+use packet_parser::{
+    capture::{
+        pcapng::{
+            Block, ParseError, PcapngOptionsIterator, PcapngPacket, PcapngReader, PcapngWriter,
+        },
+        LinkType,
+    },
+    packet::{HeaderPosition, Packet},
+};
+use std::{net::IpAddr, time::Duration};
+
+const PACKET: [u8; 64] = [
+    0x78, 0x2b, 0x46, 0x4b, 0x3b, 0xab, 0xb4, 0x8c, 0x9d, 0x5d, 0x81, 0x8b, 0x08, 0x00, 0x45, 0x00,
+    0x00, 0x32, 0x36, 0x2b, 0x40, 0x00, 0x80, 0x06, 0x08, 0x94, 0xc0, 0xa8, 0x1d, 0x11, 0xc0, 0xa8,
+    0x1d, 0xa5, 0xec, 0x62, 0x63, 0xdd, 0xc6, 0xef, 0xa3, 0xdf, 0x88, 0xce, 0x7e, 0xbc, 0x50, 0x18,
+    0x02, 0x01, 0x0e, 0x83, 0x00, 0x00, 0x08, 0xff, 0x08, 0x00, 0x07, 0x9e, 0x08, 0x00, 0x00, 0x00,
+];
+
+struct Writer {
+    big_endian: bool,
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u16(&self, value: u16) -> [u8; 2] {
+        match self.big_endian {
+            true => value.to_be_bytes(),
+            false => value.to_le_bytes(),
+        }
+    }
+
+    fn u32(&self, value: u32) -> [u8; 4] {
+        match self.big_endian {
+            true => value.to_be_bytes(),
+            false => value.to_le_bytes(),
+        }
+    }
+
+    fn padded(data: &[u8]) -> Vec<u8> {
+        let mut padded = data.to_vec();
+        padded.resize((data.len() + 3) & !3, 0);
+        padded
+    }
+
+    fn options(&self, options: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (code, value) in options {
+            bytes.extend_from_slice(&self.u16(*code));
+            bytes.extend_from_slice(&self.u16(value.len() as u16));
+            bytes.extend(Self::padded(value));
+        }
+        if !options.is_empty() {
+            bytes.extend_from_slice(&[0; 4]);
+        }
+        bytes
+    }
+
+    fn block(&mut self, block_type: u32, body: &[u8]) {
+        let length = self.u32(12 + body.len() as u32);
+        let block_type = self.u32(block_type);
+        self.bytes.extend_from_slice(&block_type);
+        self.bytes.extend_from_slice(&length);
+        self.bytes.extend_from_slice(body);
+        self.bytes.extend_from_slice(&length);
+    }
+
+    fn section(&mut self, options: &[(u16, &[u8])]) {
+        let mut body = self.u32(0x1a2b3c4d).to_vec();
+        body.extend_from_slice(&self.u16(1));
+        body.extend_from_slice(&self.u16(0));
+        body.extend_from_slice(&[0xff; 8]);
+        body.extend(self.options(options));
+        self.block(0x0a0d0d0a, &body);
+    }
+
+    fn interface(&mut self, link_type: u16, options: &[(u16, &[u8])]) {
+        let mut body = self.u16(link_type).to_vec();
+        body.extend_from_slice(&[0, 0]);
+        body.extend_from_slice(&self.u32(0));
+        body.extend(self.options(options));
+        self.block(1, &body);
+    }
+
+    fn enhanced_packet(
+        &mut self,
+        interface_id: u32,
+        timestamp: u64,
+        data: &[u8],
+        options: &[(u16, &[u8])],
+    ) {
+        let mut body = self.u32(interface_id).to_vec();
+        body.extend_from_slice(&self.u32((timestamp >> 32) as u32));
+        body.extend_from_slice(&self.u32(timestamp as u32));
+        body.extend_from_slice(&self.u32(data.len() as u32));
+        body.extend_from_slice(&self.u32(data.len() as u32));
+        body.extend(Self::padded(data));
+        body.extend(self.options(options));
+        self.block(6, &body);
+    }
+}
+
+fn sample_file() -> Vec<u8> {
+    let mut writer = Writer {
+        big_endian: false,
+        bytes: Vec::new(),
+    };
+    writer.section(&[(1, b"dumpcap capture")]);
+    writer.interface(1, &[(2, b"eth0")]);
+    writer.interface(
+        101,
+        &[(2, b"tun0"), (9, &[9]), (14, &1000i64.to_le_bytes())],
+    );
+    writer.enhanced_packet(0, 1_500_000, &PACKET, &[(1, b"first"), (1, b"second")]);
+    writer.enhanced_packet(1, 2_000_000_001, &PACKET[14..], &[]);
+    let mut simple_packet = writer.u32(PACKET.len() as u32).to_vec();
+    simple_packet.extend_from_slice(&PACKET);
+    writer.block(3, &simple_packet);
+    let mut name_resolution = writer.u16(1).to_vec();
+    name_resolution.extend_from_slice(&writer.u16(15));
+    name_resolution.extend_from_slice(b"\xc0\xa8\x1d\x11host\0alias\0\0");
+    name_resolution.extend_from_slice(&[0; 4]);
+    name_resolution.extend(writer.options(&[(1, b"dns")]));
+    writer.block(4, &name_resolution);
+    writer.block(5, &[0; 4]);
+
+    writer.bytes
+}
+
+#[test]
+fn test_pcapng_blocks() {
+    let file = sample_file();
+    let mut reader = PcapngReader::new(&file).expect("header parse failed");
+    let Some(Ok(Block::SectionHeader(section))) = reader.next() else {
+        panic!("Invalid block type")
+    };
+    assert!(!section.big_endian);
+    assert_eq!((section.version_major, section.version_minor), (1, 0));
+    assert_eq!(section.section_length, -1);
+    assert!(section.options.get_comments().eq(["dumpcap capture"]));
+
+    let Some(Ok(Block::InterfaceDescription(ethernet))) = reader.next() else {
+        panic!("Invalid block type")
+    };
+    assert_eq!(ethernet.link_type, LinkType::ETHERNET);
+    assert_eq!(ethernet.get_name(), Some("eth0"));
+    assert_eq!(ethernet.units_per_second, 1_000_000);
+    let Some(Ok(Block::InterfaceDescription(tun))) = reader.next() else {
+        panic!("Invalid block type")
+    };
+    assert_eq!(tun.link_type, LinkType::RAW);
+    assert_eq!(tun.get_name(), Some("tun0"));
+    assert_eq!(tun.units_per_second, 1_000_000_000);
+    assert_eq!(tun.timestamp_offset, 1000);
+    assert_eq!(reader.get_interfaces().len(), 2);
+
+    let Some(Ok(Block::Packet(packet))) = reader.next() else {
+        panic!("Invalid block type")
+    };
+    assert_eq!(packet.timestamp, Some(Duration::from_millis(1500)));
+    assert!(packet.options.get_comments().eq(["first", "second"]));
+    let parsed =
+        Packet::from_link_type(packet.link_type, packet.data).expect("packet parse failed");
+    assert!(parsed.get_five_tuple(HeaderPosition::Outer).is_some());
+
+    let Some(Ok(Block::Packet(packet))) = reader.next() else {
+        panic!("Invalid block type")
+    };
+    assert_eq!(packet.interface_id, 1);
+    assert_eq!(packet.link_type, LinkType::RAW);
+    assert_eq!(packet.timestamp, Some(Duration::new(1002, 1)));
+    assert_eq!(packet.data, &PACKET[14..]);
+
+    let Some(Ok(Block::Packet(packet))) = reader.next() else {
+        panic!("Invalid block type")
+    };
+    assert_eq!(packet.interface_id, 0);
+    assert_eq!(packet.timestamp, None);
+    assert_eq!(packet.data, PACKET);
+
+    let Some(Ok(Block::NameResolution(names))) = reader.next() else {
+        panic!("Invalid block type")
+    };
+    let records: Vec<_> = names.records.clone().collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].address, IpAddr::from([192, 168, 29, 17]));
+    assert!(records[0].get_names().eq(["host", "alias"]));
+    assert!(names.options.get_comments().eq(["dns"]));
+
+    assert_eq!(reader.next(), Some(Ok(Block::Unknown(5, &[0; 4]))));
+    assert_eq!(reader.next(), None);
+}
+
+#[test]
+fn test_pcapng_sections() {
+    let mut writer = Writer {
+        big_endian: false,
+        bytes: Vec::new(),
+    };
+    writer.section(&[]);
+    writer.interface(1, &[]);
+    writer.interface(1, &[]);
+    writer.enhanced_packet(1, 1, &PACKET, &[]);
+    // every section has its own byte order and interfaces
+    writer.big_endian = true;
+    writer.section(&[]);
+    writer.interface(101, &[(9, &[0x80 | 10])]);
+    writer.enhanced_packet(0, 2048, &PACKET[14..], &[]);
+    writer.enhanced_packet(1, 0, &PACKET, &[]);
+
+    let mut reader = PcapngReader::new(&writer.bytes).expect("header parse failed");
+    let blocks: Vec<_> = reader
+        .by_ref()
+        .take(5)
+        .collect::<Result<_, _>>()
+        .expect("blocks parse failed");
+    let Block::SectionHeader(section) = &blocks[4] else {
+        panic!("Invalid block type")
+    };
+    assert!(section.big_endian);
+    assert_eq!(reader.get_interfaces().len(), 0);
+    let Some(Ok(_)) = reader.next() else {
+        panic!("Invalid block type")
+    };
+    let Some(Ok(Block::Packet(packet))) = reader.next() else {
+        panic!("Invalid block type")
+    };
+    assert_eq!(packet.link_type, LinkType::RAW);
+    assert_eq!(packet.timestamp, Some(Duration::from_secs(2)));
+    assert_eq!(reader.next(), Some(Err(ParseError::UnknownInterface(1))));
+    assert_eq!(reader.next(), None);
+
+    let truncated = &writer.bytes[..writer.bytes.len() - 100];
+    let reader = PcapngReader::new(truncated).expect("header parse failed");
+    assert!(matches!(
+        reader.last(),
+        Some(Err(ParseError::TruncatedBlock(_)))
+    ));
+}
+
+#[test]
+fn test_pcapng_errors() {
+    assert_eq!(
+        PcapngReader::new(&[0xd4, 0xc3, 0xb2, 0xa1]).err(),
+        Some(ParseError::NotAPcapng)
+    );
+
+    let mut writer = Writer {
+        big_endian: false,
+        bytes: Vec::new(),
+    };
+    writer.section(&[]);
+    writer.bytes[8..12].copy_from_slice(&[1, 2, 3, 4]);
+    let mut reader = PcapngReader::new(&writer.bytes).expect("header parse failed");
+    assert_eq!(
+        reader.next(),
+        Some(Err(ParseError::InvalidByteOrderMagic(0x04030201)))
+    );
+
+    let mut writer = Writer {
+        big_endian: false,
+        bytes: Vec::new(),
+    };
+    writer.section(&[]);
+    writer.interface(1, &[]);
+    let length = writer.bytes.len();
+    writer.bytes[length - 4] = 0;
+    let mut reader = PcapngReader::new(&writer.bytes).expect("header parse failed");
+    assert!(reader.next().unwrap().is_ok());
+    assert_eq!(reader.next(), Some(Err(ParseError::InvalidBlockLength(28))));
+}
+
+#[test]
+fn test_pcapng_round_trip() {
+    let file = sample_file();
+    let mut reader = PcapngReader::new(&file).expect("header parse failed");
+    let Some(Ok(Block::SectionHeader(section))) = reader.next() else {
+        panic!("Invalid block type")
+    };
+    let mut writer = PcapngWriter::with_section(Vec::new(), &section).expect("write failed");
+    for block in reader {
+        writer
+            .write_block(&block.expect("block parse failed"))
+            .expect("write failed");
+    }
+    assert_eq!(writer.into_inner(), file);
+}
+
+#[test]
+fn test_pcapng_writer() {
+    let mut writer = PcapngWriter::new(Vec::new()).expect("write failed");
+    let ethernet = writer
+        .add_interface(LinkType::ETHERNET, 0)
+        .expect("write failed");
+    let packet = Packet::try_from(PACKET.as_slice()).expect("packet parse failed");
+    writer
+        .write_parsed_packet(ethernet, &packet, Duration::new(10, 123_000), 64)
+        .expect("write failed");
+    assert!(writer
+        .write_parsed_packet(1, &packet, Duration::ZERO, 64)
+        .is_err());
+    // the timestamp doesn't fit in 64 bits of microseconds
+    assert!(writer
+        .write_parsed_packet(ethernet, &packet, Duration::from_secs(u64::MAX), 64)
+        .is_err());
+
+    // the link type comes from the interface
+    let raw = writer
+        .add_interface(LinkType::RAW, 0)
+        .expect("write failed");
+    let ip_packet = Packet::from_ip(&PACKET[14..]).expect("packet parse failed");
+    writer
+        .write_parsed_packet(raw, &ip_packet, Duration::from_secs(20), 1500)
+        .expect("write failed");
+    // simple packet blocks belong to the first interface, so this is an enhanced packet block
+    writer
+        .write_packet(&PcapngPacket {
+            interface_id: raw,
+            link_type: LinkType::RAW,
+            timestamp: None,
+            captured_length: 50,
+            original_length: 50,
+            data: &PACKET[14..],
+            options: PcapngOptionsIterator::new(&[], false),
+        })
+        .expect("write failed");
+
+    let written = writer.into_inner();
+    let packets: Vec<_> = PcapngReader::new(&written)
+        .expect("header parse failed")
+        .packets()
+        .collect::<Result<_, _>>()
+        .expect("packets parse failed");
+    assert_eq!(packets.len(), 3);
+    assert_eq!(packets[0].link_type, LinkType::ETHERNET);
+    assert_eq!(packets[0].timestamp, Some(Duration::new(10, 123_000)));
+    assert_eq!(packets[0].data, PACKET);
+    assert_eq!(packets[1].interface_id, raw);
+    assert_eq!(packets[1].link_type, LinkType::RAW);
+    assert_eq!(packets[1].timestamp, Some(Duration::from_secs(20)));
+    assert_eq!(packets[1].original_length, 1500);
+    assert_eq!(packets[1].data, &PACKET[14..]);
+    assert_eq!(packets[2].interface_id, raw);
+    assert_eq!(packets[2].timestamp, Some(Duration::ZERO));
+    assert_eq!(packets[2].data, &PACKET[14..]);
+}
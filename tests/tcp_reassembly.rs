@@ -0,0 +1,263 @@
+mod common;
+
+use common::{tcp_segment, CLIENT, SERVER};
+use packet_parser::{
+    packet::Packet,
+    tcp_reassembly::{StreamEvent, StreamEventKind, TcpReassembler, TcpReassemblyConfig},
+    tuples::{Direction, FiveTuple},
+};
+use pnet::packet::{ip::IpNextHeaderProtocols, tcp::TcpFlags};
+use std::{net::IpAddr, time::Duration};
+
+fn process(reassembler: &mut TcpReassembler, segment: &[u8]) {
+    process_at(reassembler, segment, 0);
+}
+
+fn process_at(reassembler: &mut TcpReassembler, segment: &[u8], seconds: u64) {
+    let packet = Packet::try_from(segment).expect("packet parse failed");
+    reassembler
+        .process(&packet, Duration::from_secs(seconds))
+        .expect("segment processing failed");
+}
+
+fn events(reassembler: &mut TcpReassembler) -> Vec<(Direction, StreamEventKind)> {
+    std::iter::from_fn(|| reassembler.next_event())
+        .map(|event| (event.direction, event.kind))
+        .collect()
+}
+
+fn data(bytes: &[u8]) -> StreamEventKind {
+    StreamEventKind::Data(bytes.to_vec())
+}
+
+#[test]
+fn test_connection_lifecycle() {
+    let mut reassembler = TcpReassembler::default();
+    let client_isn = 1000;
+    let server_isn = 5000;
+
+    // the SYN-ACK arrives first, but the flow is still oriented from the client
+    process(
+        &mut reassembler,
+        &tcp_segment(false, server_isn, TcpFlags::SYN | TcpFlags::ACK, &[]),
+    );
+    process(
+        &mut reassembler,
+        &tcp_segment(true, client_isn, TcpFlags::SYN, &[]),
+    );
+    process(
+        &mut reassembler,
+        &tcp_segment(true, client_isn + 1, TcpFlags::ACK, b"GET /"),
+    );
+    assert_eq!(
+        reassembler.next_event(),
+        Some(StreamEvent {
+            flow: FiveTuple {
+                source_ip: IpAddr::from(CLIENT),
+                source_port: 40000,
+                destination_ip: IpAddr::from(SERVER),
+                destination_port: 80,
+                protocol: IpNextHeaderProtocols::Tcp,
+            },
+            direction: Direction::Forward,
+            kind: data(b"GET /"),
+        })
+    );
+
+    process(
+        &mut reassembler,
+        &tcp_segment(false, server_isn + 1, TcpFlags::ACK, b"200 OK"),
+    );
+    process(
+        &mut reassembler,
+        &tcp_segment(false, server_isn + 7, TcpFlags::FIN, &[]),
+    );
+    process(
+        &mut reassembler,
+        &tcp_segment(true, client_isn + 6, TcpFlags::FIN, &[]),
+    );
+    assert_eq!(
+        events(&mut reassembler),
+        [
+            (Direction::Reverse, data(b"200 OK")),
+            (Direction::Reverse, StreamEventKind::Fin),
+            (Direction::Forward, StreamEventKind::Fin),
+        ]
+    );
+    assert_eq!(reassembler.flow_count(), 0);
+
+    // the last ACK of the teardown doesn't open a new flow
+    process(
+        &mut reassembler,
+        &tcp_segment(false, server_isn + 8, TcpFlags::ACK, &[]),
+    );
+    assert_eq!(reassembler.next_event(), None);
+    assert_eq!(reassembler.flow_count(), 0);
+}
+
+#[test]
+fn test_out_of_order_and_retransmits() {
+    let mut reassembler = TcpReassembler::default();
+    process(&mut reassembler, &tcp_segment(true, 99, TcpFlags::SYN, &[]));
+    process(
+        &mut reassembler,
+        &tcp_segment(true, 106, TcpFlags::ACK, b"world"),
+    );
+    assert_eq!(reassembler.next_event(), None);
+    process(
+        &mut reassembler,
+        &tcp_segment(true, 100, TcpFlags::ACK, b"hello "),
+    );
+    // a retransmit of delivered data, and a segment that overlaps it
+    process(
+        &mut reassembler,
+        &tcp_segment(true, 100, TcpFlags::ACK, b"hello "),
+    );
+    process(
+        &mut reassembler,
+        &tcp_segment(true, 108, TcpFlags::ACK, b"rld!"),
+    );
+    assert_eq!(
+        events(&mut reassembler),
+        [
+            (Direction::Forward, data(b"hello ")),
+            (Direction::Forward, data(b"world")),
+            (Direction::Forward, data(b"!")),
+        ]
+    );
+}
+
+#[test]
+fn test_sequence_wraparound() {
+    let mut reassembler = TcpReassembler::default();
+    let isn = u32::MAX - 3;
+    process(
+        &mut reassembler,
+        &tcp_segment(true, isn, TcpFlags::SYN, &[]),
+    );
+    process(
+        &mut reassembler,
+        &tcp_segment(true, isn.wrapping_add(7), TcpFlags::ACK, b"after"),
+    );
+    process(
+        &mut reassembler,
+        &tcp_segment(true, isn + 1, TcpFlags::ACK, b"before"),
+    );
+    assert_eq!(
+        events(&mut reassembler),
+        [
+            (Direction::Forward, data(b"before")),
+            (Direction::Forward, data(b"after")),
+        ]
+    );
+}
+
+#[test]
+fn test_gaps() {
+    let mut reassembler = TcpReassembler::new(TcpReassemblyConfig {
+        max_buffered_bytes: 8,
+        ..Default::default()
+    });
+    process(&mut reassembler, &tcp_segment(true, 0, TcpFlags::SYN, &[]));
+    process(
+        &mut reassembler,
+        &tcp_segment(true, 11, TcpFlags::ACK, b"1234"),
+    );
+    assert_eq!(reassembler.next_event(), None);
+    // the buffer limit is reached, so the missing bytes are skipped
+    process(
+        &mut reassembler,
+        &tcp_segment(true, 15, TcpFlags::ACK, b"56789"),
+    );
+    assert_eq!(
+        events(&mut reassembler),
+        [
+            (Direction::Forward, StreamEventKind::Gap(10)),
+            (Direction::Forward, data(b"1234")),
+            (Direction::Forward, data(b"56789")),
+        ]
+    );
+
+    process(
+        &mut reassembler,
+        &tcp_segment(false, 0, TcpFlags::SYN | TcpFlags::ACK, &[]),
+    );
+    process(
+        &mut reassembler,
+        &tcp_segment(false, 4, TcpFlags::ACK, b"late"),
+    );
+    reassembler.flush();
+    assert_eq!(
+        events(&mut reassembler),
+        [
+            (Direction::Reverse, StreamEventKind::Gap(3)),
+            (Direction::Reverse, data(b"late")),
+        ]
+    );
+    assert_eq!(reassembler.flow_count(), 0);
+}
+
+#[test]
+fn test_reset() {
+    let mut reassembler = TcpReassembler::default();
+    process(&mut reassembler, &tcp_segment(true, 0, TcpFlags::SYN, &[]));
+    process(
+        &mut reassembler,
+        &tcp_segment(false, 0, TcpFlags::RST | TcpFlags::ACK, &[]),
+    );
+    assert_eq!(
+        events(&mut reassembler),
+        [(Direction::Reverse, StreamEventKind::Reset)]
+    );
+    assert_eq!(reassembler.flow_count(), 0);
+
+    // the out of order data is delivered before the reset
+    process(&mut reassembler, &tcp_segment(true, 0, TcpFlags::SYN, &[]));
+    process(
+        &mut reassembler,
+        &tcp_segment(true, 5, TcpFlags::ACK, b"tail"),
+    );
+    process(&mut reassembler, &tcp_segment(true, 9, TcpFlags::RST, &[]));
+    assert_eq!(
+        events(&mut reassembler),
+        [
+            (Direction::Forward, StreamEventKind::Gap(4)),
+            (Direction::Forward, data(b"tail")),
+            (Direction::Forward, StreamEventKind::Reset),
+        ]
+    );
+    assert_eq!(reassembler.flow_count(), 0);
+}
+
+#[test]
+fn test_idle_expiry() {
+    let mut reassembler = TcpReassembler::new(TcpReassemblyConfig {
+        idle_timeout: Duration::from_secs(10),
+        ..Default::default()
+    });
+    // a half open connection, with data that will never be completed
+    process_at(
+        &mut reassembler,
+        &tcp_segment(true, 0, TcpFlags::SYN, &[]),
+        0,
+    );
+    process_at(
+        &mut reassembler,
+        &tcp_segment(true, 5, TcpFlags::ACK, b"tail"),
+        5,
+    );
+    assert_eq!(reassembler.expire(Duration::from_secs(14)), 0);
+    assert_eq!(reassembler.flow_count(), 1);
+    assert_eq!(reassembler.next_event(), None);
+
+    assert_eq!(reassembler.expire(Duration::from_secs(15)), 1);
+    assert_eq!(reassembler.flow_count(), 0);
+    assert_eq!(
+        events(&mut reassembler),
+        [
+            (Direction::Forward, StreamEventKind::Gap(4)),
+            (Direction::Forward, data(b"tail")),
+            (Direction::Forward, StreamEventKind::Expired),
+        ]
+    );
+}
@@ -1,15 +1,28 @@
 use packet_parser::{
-    l2::L2Packet,
-    l3::L3Packet,
-    l3_extensions::ipv4_options::{Ipv4Option, Ipv4ZeroCopyOptionsIterator},
-    l4::L4Packet,
+    capture::LinkType,
+    l2::{self, L2Packet},
+    l3::{self, L3Packet, MplsPayload, PppoePayload},
+    l3_extensions::{
+        ipv4_options::{Ipv4Option, Ipv4ZeroCopyOptionsIterator},
+        ipv6_extensions,
+    },
+    l4::{self, L4Packet},
     l4_extensions::tcp_options::{TcpOption, TcpZeroCopyOptionsIterator},
-    packet::{HeaderPosition, Packet},
+    packet::{Encapsulation, HeaderPosition, Level, Packet, ParseError},
+    parse_options::{MplsPseudowire, ParseOptions},
+    protocols::{
+        llc::saps,
+        loopback::address_families,
+        ppp::{control_codes, PppControl},
+        pppoe::{codes, tag_types},
+    },
     tuples::{FiveTuple, FourTuple},
 };
 use pnet::packet::{
-    icmp::IcmpPacket, ip::IpNextHeaderProtocols, ipv4::Ipv4OptionNumber, tcp::TcpOptionNumbers,
+    ethernet::EtherTypes, icmp::IcmpPacket, ip::IpNextHeaderProtocols, ipv4::Ipv4OptionNumber,
+    tcp::TcpOptionNumbers,
 };
+use smallvec::smallvec;
 use std::net::IpAddr;
 
 #[test]
@@ -70,15 +83,291 @@ fn test_gre() {
         let expected_icmp = IcmpPacket::new(&packet[58..]).expect("Parsing Icmp manually failed");
 
         assert!(matches!(
-            parsed,
-            Packet::L3Tunnel(
-                L2Packet::Ethernet (
+            parsed.get_levels(),
+            [
+                Level::L2(None, L2Packet::Ethernet (
                     _,
                     _,
                     L3Packet::Ipv4(_, L4Packet::Gre(_))
-                ),
-                L3Packet::Ipv4(_, L4Packet::Icmp(header))
-            ) if header == expected_icmp
+                )),
+                Level::L3(None, L3Packet::Ipv4(_, L4Packet::Icmp(header)))
+            ] if *header == expected_icmp
+        ));
+
+        // unsupported GRE protocols aren't decapsulated, their payload is the GRE payload
+        let mut unknown_protocol_packet = *packet;
+        unknown_protocol_packet[36..38].copy_from_slice(&[0x88, 0xb5]);
+        let parsed = Packet::try_from(unknown_protocol_packet.as_slice()).expect("packet parse failed");
+        assert!(matches!(parsed.get_levels(), [Level::L2(None, _)]));
+        assert_eq!(parsed.get_payload(), Some(&unknown_protocol_packet[38..]));
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Innermost).and_then(L3Packet::get_source),
+            Some(IpAddr::from([10, 0, 0, 1]))
+        );
+
+        // the acknowledgement flag is reserved in version 0, and doesn't add a field
+        let mut reserved_flag_packet = *packet;
+        reserved_flag_packet[35] = 0x80;
+        let parsed = Packet::try_from(reserved_flag_packet.as_slice()).expect("packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Gre(gre)))), Level::L3(None, L3Packet::Ipv4(_, L4Packet::Icmp(_)))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert!(!gre.get_acknowledgement_present());
+        assert_eq!(gre.get_acknowledgement(), None);
+        assert_eq!(gre.get_header_length(), 4);
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_gre_optional_fields() {
+    let allocations = allocation_counter::measure(|| {
+        let checksum_packet = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x00, 0x01, 0x02, 0x03, 0x04, 0x06, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x44, 0x00, 0x00, 0x00, 0x00, 0x40, 0x2f, 0x66, 0x89, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0xb0, 0x00, 0x08, 0x00, 0x43, 0xf2, 0x00, 0x00,
+            0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x00, 0x07, 0x45, 0x00, 0x00, 0x20, 0x00, 0x00,
+            0x00, 0x00, 0x40, 0x01, 0x74, 0xd8, 0x01, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02,
+            0x08, 0x00, 0xa0, 0x62, 0x00, 0x01, 0x00, 0x01, 0xab, 0xcd, 0xab, 0xcd,
+        ];
+
+        let parsed = Packet::try_from(checksum_packet.as_slice()).expect("packet parse failed");
+        let expected_icmp =
+            IcmpPacket::new(&checksum_packet[70..]).expect("Parsing Icmp manually failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Gre(gre)))), Level::L3(None, L3Packet::Ipv4(_, L4Packet::Icmp(icmp)))] = parsed.get_levels() else {
+            panic!("expected icmp in gre");
+        };
+        assert_eq!(*icmp, expected_icmp);
+        assert!(gre.get_checksum_present() && gre.get_key_present() && gre.get_sequence_present());
+        assert!(!gre.get_routing_present());
+        assert_eq!(gre.get_version(), 0);
+        assert_eq!(gre.get_protocol_type(), EtherTypes::Ipv4);
+        assert_eq!(gre.get_checksum(), Some(0x43f2));
+        assert_eq!(gre.verify_checksum(), Some(true));
+        assert_eq!(gre.get_key(), Some(0x01020304));
+        assert_eq!(gre.get_sequence(), Some(7));
+        assert_eq!(gre.get_header_length(), 16);
+        assert_eq!(gre.get_routing().count(), 0);
+
+        // the checksum covers the payload as well
+        let mut corrupted_packet = checksum_packet;
+        corrupted_packet[81] = 0xce;
+        let parsed = Packet::try_from(corrupted_packet.as_slice()).expect("packet parse failed");
+        let Some(L4Packet::Gre(gre)) = parsed.get_l4(HeaderPosition::Outer) else {
+            panic!("expected gre");
+        };
+        assert_eq!(gre.verify_checksum(), Some(false));
+
+        let routing_packet = &[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x00, 0x01, 0x02, 0x03, 0x04, 0x06, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x50, 0x00, 0x00, 0x00, 0x00, 0x40, 0x2f, 0x66, 0x7d, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x60, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x04,
+            0x01, 0x02, 0x03, 0x04, 0x08, 0x00, 0x00, 0x08, 0x0a, 0x00, 0x00, 0x03, 0x0a, 0x00,
+            0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00,
+            0x40, 0x01, 0x74, 0xd8, 0x01, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x08, 0x00,
+            0xa0, 0x62, 0x00, 0x01, 0x00, 0x01, 0xab, 0xcd, 0xab, 0xcd,
+        ];
+
+        let parsed = Packet::try_from(routing_packet.as_slice()).expect("packet parse failed");
+        let expected_icmp =
+            IcmpPacket::new(&routing_packet[82..]).expect("Parsing Icmp manually failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Gre(gre)))), Level::L3(None, L3Packet::Ipv4(_, L4Packet::Icmp(icmp)))] = parsed.get_levels() else {
+            panic!("expected icmp in gre");
+        };
+        assert_eq!(*icmp, expected_icmp);
+        assert!(gre.get_routing_present() && gre.get_key_present());
+        // the checksum field is present with routing, but isn't valid
+        assert_eq!(gre.get_checksum(), None);
+        assert_eq!(gre.verify_checksum(), None);
+        assert_eq!(gre.get_offset(), Some(4));
+        assert_eq!(gre.get_key(), Some(0x01020304));
+        assert_eq!(gre.get_sequence(), None);
+        assert_eq!(gre.get_header_length(), 28);
+        let mut routing = gre.get_routing();
+        let entry = routing.next().expect("expected a source route entry");
+        assert_eq!(entry.get_address_family(), 0x0800);
+        assert_eq!(entry.get_sre_offset(), 0);
+        assert_eq!(entry.get_sre_length(), 8);
+        assert_eq!(entry.get_routing_information(), &[10, 0, 0, 3, 10, 0, 0, 2]);
+        assert!(routing.next().is_none());
+
+        // a source route that isn't terminated with a null entry
+        assert!(Packet::try_from(&routing_packet[..62]).is_err());
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_nvgre() {
+    let allocations = allocation_counter::measure(|| {
+        let packet = &[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x00, 0x01, 0x02, 0x03, 0x04, 0x06, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00, 0x40, 0x2f, 0x66, 0x83, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x20, 0x00, 0x65, 0x58, 0x12, 0x34, 0x56, 0x7f,
+            0x00, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x00, 0x0a, 0x0b, 0x0c, 0x0d, 0x0f, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x40, 0x01, 0x74, 0xd8, 0x01, 0x01,
+            0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x08, 0x00, 0xa0, 0x62, 0x00, 0x01, 0x00, 0x01,
+            0xab, 0xcd, 0xab, 0xcd,
+        ];
+
+        let parsed = Packet::try_from(packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Gre(gre)))), Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Icmp(_))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(gre.get_protocol_type(), l2::TRANSPARENT_ETHERNET_BRIDGING);
+        assert!(gre.is_nvgre());
+        assert_eq!(gre.get_key(), Some(0x1234567f));
+        assert_eq!(gre.get_vsid(), Some(0x123456));
+        assert_eq!(gre.get_flow_id(), Some(0x7f));
+        assert_eq!(
+            parsed.get_l2(HeaderPosition::Inner).map(|l2| l2.get_source_address()),
+            Some([0x00, 0x0a, 0x0b, 0x0c, 0x0d, 0x0f].as_slice())
+        );
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Innermost).and_then(|l3| l3.get_source()),
+            Some(IpAddr::from([1, 1, 1, 1]))
+        );
+        assert_eq!(
+            parsed.get_payload(),
+            Some([0x00, 0x01, 0x00, 0x01, 0xab, 0xcd, 0xab, 0xcd].as_slice())
+        );
+        assert_eq!(parsed.get_bytes(), packet.as_slice());
+
+        // ethernet over GRE without a key isn't NVGRE, but is still decapsulated
+        let mut keyless_packet = [0; 84];
+        keyless_packet[..34].copy_from_slice(&packet[..34]);
+        keyless_packet[34..38].copy_from_slice(&[0x00, 0x00, 0x65, 0x58]);
+        keyless_packet[38..].copy_from_slice(&packet[42..]);
+        keyless_packet[17] = 0x46;
+        let parsed = Packet::try_from(keyless_packet.as_slice()).expect("Packet parse failed");
+        let Some(L4Packet::Gre(gre)) = parsed.get_l4(HeaderPosition::Outer) else {panic!("expected gre")};
+        assert!(!gre.is_nvgre());
+        assert_eq!(gre.get_vsid(), None);
+        assert!(parsed.get_l2(HeaderPosition::Inner).is_some());
+
+        // NVGRE has no sequence number, so a keyed and sequenced header isn't NVGRE
+        let mut sequenced_packet = [0; 92];
+        sequenced_packet[..42].copy_from_slice(&packet[..42]);
+        sequenced_packet[34] = 0x30;
+        sequenced_packet[42..46].copy_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+        sequenced_packet[46..].copy_from_slice(&packet[42..]);
+        sequenced_packet[17] = 0x4e;
+        let parsed = Packet::try_from(sequenced_packet.as_slice()).expect("Packet parse failed");
+        let Some(L4Packet::Gre(gre)) = parsed.get_l4(HeaderPosition::Outer) else {panic!("expected gre")};
+        assert!(!gre.is_nvgre());
+        assert_eq!(gre.get_vsid(), None);
+        assert_eq!(gre.get_sequence(), Some(1));
+        assert!(parsed.get_l2(HeaderPosition::Inner).is_some());
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_erspan() {
+    let allocations = allocation_counter::measure(|| {
+        let type_i_packet = &[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x00, 0x01, 0x02, 0x03, 0x04, 0x06, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x46, 0x00, 0x00, 0x00, 0x00, 0x40, 0x2f, 0x66, 0x87, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x00, 0x00, 0x88, 0xbe, 0x00, 0x0a, 0x0b, 0x0c,
+            0x0d, 0x0e, 0x00, 0x0a, 0x0b, 0x0c, 0x0d, 0x0f, 0x08, 0x00, 0x45, 0x00, 0x00, 0x20,
+            0x00, 0x00, 0x00, 0x00, 0x40, 0x01, 0x74, 0xd8, 0x01, 0x01, 0x01, 0x01, 0x02, 0x02,
+            0x02, 0x02, 0x08, 0x00, 0xa0, 0x62, 0x00, 0x01, 0x00, 0x01, 0xab, 0xcd, 0xab, 0xcd,
+        ];
+
+        let parsed = Packet::try_from(type_i_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Gre(_)))), Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Icmp(_))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(
+            parsed.get_l2(HeaderPosition::Inner).map(|l2| l2.get_source_address()),
+            Some([0x00, 0x0a, 0x0b, 0x0c, 0x0d, 0x0f].as_slice())
+        );
+
+        let type_ii_packet = &[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x00, 0x01, 0x02, 0x03, 0x04, 0x06, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x52, 0x00, 0x00, 0x00, 0x00, 0x40, 0x2f, 0x66, 0x7b, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x10, 0x00, 0x88, 0xbe, 0x00, 0x00, 0x00, 0x01,
+            0x10, 0x64, 0xa1, 0x23, 0x00, 0x05, 0x43, 0x21, 0x00, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+            0x00, 0x0a, 0x0b, 0x0c, 0x0d, 0x0f, 0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x00,
+            0x00, 0x00, 0x40, 0x01, 0x74, 0xd8, 0x01, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02,
+            0x08, 0x00, 0xa0, 0x62, 0x00, 0x01, 0x00, 0x01, 0xab, 0xcd, 0xab, 0xcd,
+        ];
+
+        let parsed = Packet::try_from(type_ii_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, _), Level::L2(Some(Encapsulation::Erspan(erspan)), L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Icmp(_))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(erspan.get_version(), 1);
+        assert_eq!(erspan.get_vlan(), 100);
+        assert_eq!(erspan.get_cos(), 5);
+        assert_eq!(erspan.get_encapsulation_type(), Some(0));
+        assert!(!erspan.get_truncated());
+        assert_eq!(erspan.get_session_id(), 0x123);
+        assert_eq!(erspan.get_index(), Some(0x54321));
+        assert_eq!(erspan.get_timestamp(), None);
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Innermost).and_then(|l3| l3.get_source()),
+            Some(IpAddr::from([1, 1, 1, 1]))
+        );
+        assert_eq!(parsed.get_bytes(), type_ii_packet.as_slice());
+
+        let type_iii_packet = &[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x00, 0x01, 0x02, 0x03, 0x04, 0x06, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x5e, 0x00, 0x00, 0x00, 0x00, 0x40, 0x2f, 0x66, 0x6f, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x10, 0x00, 0x22, 0xeb, 0x00, 0x00, 0x00, 0x02,
+            0x20, 0x00, 0x04, 0x07, 0xde, 0xad, 0xbe, 0xef, 0x01, 0x02, 0x02, 0xaf, 0x01, 0x02,
+            0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x00, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x00, 0x0a,
+            0x0b, 0x0c, 0x0d, 0x0f, 0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00,
+            0x40, 0x01, 0x74, 0xd8, 0x01, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x08, 0x00,
+            0xa0, 0x62, 0x00, 0x01, 0x00, 0x01, 0xab, 0xcd, 0xab, 0xcd,
+        ];
+
+        let parsed = Packet::try_from(type_iii_packet.as_slice()).expect("Packet parse failed");
+        let Some(Encapsulation::Erspan(erspan)) = parsed.get_encapsulation(HeaderPosition::Inner) else {panic!("expected erspan")};
+        assert_eq!(erspan.get_version(), 2);
+        assert!(erspan.get_truncated());
+        assert_eq!(erspan.get_session_id(), 7);
+        assert_eq!(erspan.get_index(), None);
+        assert_eq!(erspan.get_timestamp(), Some(0xdeadbeef));
+        assert_eq!(erspan.get_sgt(), Some(0x0102));
+        assert_eq!(erspan.get_frame_type(), Some(0));
+        assert_eq!(erspan.get_hardware_id(), Some(0x2a));
+        assert_eq!(erspan.get_direction(), Some(true));
+        assert_eq!(erspan.get_granularity(), Some(3));
+        assert_eq!(erspan.get_platform_subheader(), Some([1, 2, 3, 4, 5, 6, 7, 8].as_slice()));
+        assert_eq!(erspan.get_header_length(), 20);
+        assert_eq!(
+            parsed.get_l2(HeaderPosition::Inner).map(|l2| l2.get_source_address()),
+            Some([0x00, 0x0a, 0x0b, 0x0c, 0x0d, 0x0f].as_slice())
+        );
+
+        // a mirrored IP packet, without its ethernet header
+        let mut ip_frame_packet = [0; 94];
+        ip_frame_packet[..62].copy_from_slice(&type_iii_packet[..62]);
+        ip_frame_packet[62..].copy_from_slice(&type_iii_packet[76..]);
+        ip_frame_packet[17] = 0x50;
+        ip_frame_packet[52] = 0x0a;
+        let parsed = Packet::try_from(ip_frame_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, _), Level::L3(Some(Encapsulation::Erspan(erspan)), L3Packet::Ipv4(_, L4Packet::Icmp(_)))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(erspan.get_frame_type(), Some(2));
+        assert_eq!(erspan.get_hardware_id(), Some(0x2a));
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Inner).and_then(|l3| l3.get_source()),
+            Some(IpAddr::from([1, 1, 1, 1]))
+        );
+
+        // other frame types aren't decapsulated
+        let mut unknown_frame_packet = *type_iii_packet;
+        unknown_frame_packet[52] = 0x06;
+        let parsed = Packet::try_from(unknown_frame_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [Level::L2(None, _)]));
+
+        // the version of the header must match the GRE protocol
+        let mut mismatched_version_packet = *type_iii_packet;
+        mismatched_version_packet[36..38].copy_from_slice(&[0x88, 0xbe]);
+        assert!(matches!(
+            Packet::try_from(mismatched_version_packet.as_slice()),
+            Err(ParseError::Erspan)
+        ));
+
+        let mut truncated_packet = [0; 52];
+        truncated_packet.copy_from_slice(&type_iii_packet[..52]);
+        truncated_packet[17] = 0x26;
+        assert!(matches!(
+            Packet::try_from(truncated_packet.as_slice()),
+            Err(ParseError::Erspan)
         ));
     });
     assert_eq!(allocations.count_total, 0, "allocations detected");
@@ -100,12 +389,12 @@ fn test_with_payload() {
         let parsed = Packet::try_from(packet.as_slice()).expect("Packet parse failed");
 
         assert!(matches!(
-            parsed,
-            Packet::Regular(L2Packet::Ethernet(
+            parsed.get_levels(),
+            [Level::L2(None, L2Packet::Ethernet(
                 _,
                 _,
                 L3Packet::Ipv4(_, L4Packet::Tcp(_))
-            ))
+            ))]
         ));
     });
     assert_eq!(allocations.count_total, 0, "allocations detected");
@@ -127,7 +416,7 @@ fn test_ipv4_options() {
             0x2c, 0x2d, 0x2e, 0x2f, 0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37,
         ];
         let parsed = Packet::try_from(packet.as_slice()).expect("Packet parse failed");
-        let Packet::Regular(L2Packet::Ethernet(_, _, L3Packet::Ipv4(ipv4, _))) = &parsed else {panic!("Invalid packet type")};
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(ipv4, _)))] = parsed.get_levels() else {panic!("Invalid packet type")};
         let mut options = ipv4.get_options_zero_copy();
         assert_eq!(
             options.next(),
@@ -143,6 +432,22 @@ fn test_ipv4_options() {
             })
         );
         assert_eq!(options.next(), None);
+
+        let limit = ParseOptions {
+            max_options: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Packet::parse_with(packet.as_slice(), &limit),
+            Err(ParseError::L2Error(l2::ParseError::L3Error(
+                l3::ParseError::TooManyIpv4Options
+            )))
+        ));
+        let limit = ParseOptions {
+            max_options: Some(1),
+            ..Default::default()
+        };
+        assert!(Packet::parse_with(packet.as_slice(), &limit).is_ok());
     });
     assert_eq!(allocations.count_total, 0, "allocations detected");
 }
@@ -163,14 +468,14 @@ fn test_ipv6_extensions() {
         ];
         let parsed = Packet::try_from(packet.as_slice()).expect("Packet parse failed");
         assert!(matches!(
-            parsed,
-            Packet::Regular(L2Packet::Ethernet(
+            parsed.get_levels(),
+            [Level::L2(None, L2Packet::Ethernet(
                 _,
                 _,
                 L3Packet::Ipv6(_, _, L4Packet::Udp(_))
-            ))
+            ))]
         ));
-        let Packet::Regular(L2Packet::Ethernet(_, _, L3Packet::Ipv6(_, extensions, L4Packet::Udp(udp_header)))) = &parsed else {panic!("Invalid packet type")};
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv6(_, extensions, L4Packet::Udp(udp_header))))] = parsed.get_levels() else {panic!("Invalid packet type")};
         assert_eq!(extensions.extensions.len(), 1);
         assert_eq!(extensions.extensions[0].packet.get_hdr_ext_len(), 4);
         assert_eq!(
@@ -181,6 +486,22 @@ fn test_ipv6_extensions() {
         assert_eq!(udp_header.get_source(), 53);
         assert_eq!(udp_header.get_destination(), 53);
         assert_eq!(udp_header.get_length(), 14);
+
+        let options = ParseOptions {
+            max_ipv6_extensions: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Packet::parse_with(packet.as_slice(), &options),
+            Err(ParseError::L2Error(l2::ParseError::L3Error(
+                l3::ParseError::Ipv6ExtensionError(ipv6_extensions::ParseError::TooManyExtensions)
+            )))
+        ));
+        let options = ParseOptions {
+            max_ipv6_extensions: Some(1),
+            ..Default::default()
+        };
+        assert!(Packet::parse_with(packet.as_slice(), &options).is_ok());
     });
     assert_eq!(allocations.count_total, 0, "allocations detected");
 }
@@ -199,14 +520,14 @@ fn test_tcp_options() {
         ];
         let parsed = Packet::try_from(packet.as_slice()).expect("Packet parse failed");
         assert!(matches!(
-            parsed,
-            Packet::Regular(L2Packet::Ethernet(
+            parsed.get_levels(),
+            [Level::L2(None, L2Packet::Ethernet(
                 _,
                 _,
                 L3Packet::Ipv4(_, L4Packet::Tcp(_))
-            ))
+            ))]
         ));
-        let Packet::Regular(L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Tcp(tcp)))) = &parsed else {panic!("Invalid packet type")};
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Tcp(tcp))))] = parsed.get_levels() else {panic!("Invalid packet type")};
         let mut options = tcp.get_options_zero_copy();
         assert_eq!(
             options.next(),
@@ -235,3 +556,1100 @@ fn test_tcp_options() {
     });
     assert_eq!(allocations.count_total, 0, "allocations detected");
 }
+
+#[test]
+fn test_qinq() {
+    let allocations = allocation_counter::measure(|| {
+        let packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x88, 0xa8,
+            0x00, 0x64, 0x81, 0x00, 0x00, 0xc8, 0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01,
+            0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+            0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::try_from(packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, l2)] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert!(matches!(l2.get_l3(), Some(L3Packet::Ipv4(_, L4Packet::Udp(_)))));
+
+        assert_eq!(l2.get_vlans().len(), 2);
+        assert_eq!(l2.get_vlan_tpid_at(0), Some(EtherTypes::PBridge));
+        assert_eq!(l2.get_vlan_at(0).map(|vlan| vlan.get_vlan_identifier()), Some(100));
+        assert_eq!(l2.get_vlan_tpid_at(1), Some(EtherTypes::Vlan));
+        assert_eq!(l2.get_vlan_at(1).map(|vlan| vlan.get_vlan_identifier()), Some(200));
+        assert_eq!(l2.get_vlan_tpid_at(2), None);
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_parse_options() {
+    let allocations = allocation_counter::measure(|| {
+        let qinq_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x88, 0xa8,
+            0x00, 0x64, 0x81, 0x00, 0x00, 0xc8, 0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01,
+            0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+            0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let options = ParseOptions {
+            max_vlan_tags: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Packet::parse_with(qinq_packet.as_slice(), &options),
+            Err(ParseError::L2Error(l2::ParseError::TooManyVlans))
+        ));
+        let options = ParseOptions {
+            max_vlan_tags: Some(2),
+            ..Default::default()
+        };
+        assert!(Packet::parse_with(qinq_packet.as_slice(), &options).is_ok());
+
+        let tcp_options_packet = &[
+            0x40, 0x16, 0x7e, 0x22, 0xbc, 0xdf, 0xb4, 0xb0, 0x24, 0xf3, 0xf8, 0x10, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x34, 0x04, 0xe5, 0x40, 0x00, 0x3a, 0x06, 0xb0, 0xfc, 0x68, 0x11,
+            0x61, 0x6c, 0xc0, 0xa8, 0x00, 0xbd, 0x01, 0xbb, 0x09, 0x84, 0x36, 0xe9, 0x95, 0x73,
+            0x83, 0x5a, 0x1d, 0xac, 0x80, 0x10, 0x00, 0x08, 0x34, 0x24, 0x00, 0x00, 0x01, 0x01,
+            0x05, 0x0a, 0x83, 0x5a, 0x1d, 0xab, 0x83, 0x5a, 0x1d, 0xac,
+        ];
+        let options = ParseOptions {
+            max_options: Some(2),
+            ..Default::default()
+        };
+        assert!(matches!(
+            L2Packet::parse_with(tcp_options_packet.as_slice(), &options),
+            Err(l2::ParseError::L3Error(l3::ParseError::L4Error(
+                l4::ParseError::TooManyTcpOptions
+            )))
+        ));
+
+        let gre_packet = &[
+            0xc2, 0x01, 0x57, 0x75, 0x00, 0x00, 0xc2, 0x00, 0x57, 0x75, 0x00, 0x00, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x2c, 0x00, 0x0a, 0x00, 0x00, 0xff, 0x2f, 0xa7, 0x46, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x00, 0x00, 0x08, 0x00, 0x45, 0x00, 0x00, 0x14,
+            0x00, 0x0a, 0x00, 0x00, 0xff, 0x01, 0xb5, 0x89, 0x01, 0x01, 0x01, 0x01, 0x02, 0x02,
+            0x02, 0x02,
+        ];
+        let options = ParseOptions {
+            max_tunnel_depth: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Packet::parse_with(gre_packet.as_slice(), &options),
+            Err(ParseError::TunnelDepthExceeded)
+        ));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_arp_and_unknown_ethertype() {
+    let allocations = allocation_counter::measure(|| {
+        let arp_packet = &[
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x08, 0x06,
+            0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55,
+            0x0a, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x02,
+        ];
+        let parsed = Packet::try_from(arp_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(
+            parsed.get_levels(),
+            [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Arp(_)))]
+        ));
+        assert!(parsed.get_l4(HeaderPosition::Innermost).is_none());
+        assert!(parsed.get_five_tuple(HeaderPosition::Innermost).is_none());
+
+        let lldp_packet = &[
+            0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x88, 0xcc,
+            0x02, 0x07, 0x04, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x04, 0x02, 0x07, 0x31, 0x06,
+            0x02, 0x00, 0x78, 0x00, 0x00,
+        ];
+        let parsed = Packet::try_from(lldp_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(
+            parsed.get_levels(),
+            [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Unknown(EtherTypes::Lldp, payload)))]
+                if *payload == &lldp_packet[14..]
+        ));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_unknown_l4() {
+    let allocations = allocation_counter::measure(|| {
+        let packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x40, 0x84, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x0b, 0x59, 0x0b, 0x59, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        let parsed = Packet::try_from(packet.as_slice()).expect("Packet parse failed");
+        let l3 = parsed.get_l3(HeaderPosition::Innermost).expect("missing l3");
+        assert!(matches!(
+            l3,
+            L3Packet::Ipv4(_, L4Packet::Unknown(IpNextHeaderProtocols::Sctp, _))
+        ));
+        assert_eq!(l3.get_source(), Some(IpAddr::from([10, 0, 0, 1])));
+        assert_eq!(l3.get_destination(), Some(IpAddr::from([10, 0, 0, 2])));
+        assert_eq!(l3.get_l4_protocol(), Some(IpNextHeaderProtocols::Sctp));
+        assert_eq!(parsed.get_payload(), Some(&packet[34..]));
+        assert!(parsed.get_four_tuple(HeaderPosition::Innermost).is_none());
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_ethernet_padding() {
+    let allocations = allocation_counter::measure(|| {
+        let packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00,
+            0xde, 0xad, 0xbe, 0xef, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        let parsed = Packet::try_from(packet.as_slice()).expect("Packet parse failed");
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+        let l2 = parsed.get_l2(HeaderPosition::Outer).expect("missing l2");
+        assert_eq!(l2.get_trailer(), &[0; 14]);
+
+        // a total length of 0 (TSO) means the whole buffer is the IP packet
+        let mut tso_packet = [0; 46];
+        tso_packet.copy_from_slice(&packet[..46]);
+        tso_packet[16..18].copy_from_slice(&[0, 0]);
+        let parsed = Packet::try_from(tso_packet.as_slice()).expect("Packet parse failed");
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_truncated_ip() {
+    let allocations = allocation_counter::measure(|| {
+        let packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x40, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35, 0x00, 0x2c, 0x00, 0x00,
+            0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::try_from(packet.as_slice()).expect("Packet parse failed");
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+
+        let options = ParseOptions {
+            strict_ip_length: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Packet::parse_with(packet.as_slice(), &options),
+            Err(ParseError::L2Error(l2::ParseError::L3Error(
+                l3::ParseError::TruncatedIPv4
+            )))
+        ));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_ipv4_fragments() {
+    let allocations = allocation_counter::measure(|| {
+        let first_fragment = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x1c, 0x12, 0x34, 0x20, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35, 0x01, 0x00, 0x00, 0x00,
+        ];
+        let parsed = Packet::try_from(first_fragment.as_slice()).expect("Packet parse failed");
+        let l3 = parsed.get_l3(HeaderPosition::Innermost).expect("missing l3");
+        assert!(matches!(l3, L3Packet::Ipv4(_, L4Packet::Udp(_))));
+        assert!(l3.is_fragment());
+        assert!(l3.more_fragments());
+        assert_eq!(l3.fragment_offset(), Some(0));
+        assert_eq!(l3.fragment_identification(), Some(0x1234));
+
+        let last_fragment = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x1c, 0x12, 0x34, 0x00, 0xb9, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        ];
+        let parsed = Packet::try_from(last_fragment.as_slice()).expect("Packet parse failed");
+        let l3 = parsed.get_l3(HeaderPosition::Innermost).expect("missing l3");
+        assert!(matches!(
+            l3,
+            L3Packet::Ipv4(_, L4Packet::Fragment(payload)) if *payload == &last_fragment[34..]
+        ));
+        assert!(l3.is_fragment());
+        assert!(!l3.more_fragments());
+        assert_eq!(l3.fragment_offset(), Some(1480));
+        assert_eq!(l3.get_l4_protocol(), Some(IpNextHeaderProtocols::Udp));
+        assert!(parsed.get_four_tuple(HeaderPosition::Innermost).is_none());
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_ipv6_fragments() {
+    let allocations = allocation_counter::measure(|| {
+        let packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x86, 0xdd,
+            0x60, 0x00, 0x00, 0x00, 0x00, 0x10, 0x2c, 0x40, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x20, 0x01, 0x0d, 0xb8,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x11, 0x00,
+            0x05, 0xa9, 0x00, 0x00, 0xab, 0xcd, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        ];
+        let parsed = Packet::try_from(packet.as_slice()).expect("Packet parse failed");
+        let l3 = parsed.get_l3(HeaderPosition::Innermost).expect("missing l3");
+        assert!(matches!(
+            l3,
+            L3Packet::Ipv6(_, _, L4Packet::Fragment(payload)) if *payload == &packet[62..]
+        ));
+        assert!(l3.is_fragment());
+        assert!(l3.more_fragments());
+        assert_eq!(l3.fragment_offset(), Some(1448));
+        assert_eq!(l3.fragment_identification(), Some(0xabcd));
+        assert_eq!(l3.get_l4_protocol(), Some(IpNextHeaderProtocols::Udp));
+
+        // the payload of a non-first fragment isn't parsed as the extensions that follow it
+        let mut options_packet = *packet;
+        options_packet[54] = IpNextHeaderProtocols::Ipv6Opts.0;
+        options_packet[62..].fill(0xff);
+        let parsed = Packet::try_from(options_packet.as_slice()).expect("Packet parse failed");
+        let Some(L3Packet::Ipv6(_, extensions, L4Packet::Fragment(payload))) = parsed.get_l3(HeaderPosition::Innermost) else {panic!("Invalid packet type")};
+        assert_eq!(extensions.extensions.len(), 1);
+        assert_eq!(extensions.next_protocol, IpNextHeaderProtocols::Ipv6Opts);
+        assert_eq!(*payload, &options_packet[62..]);
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_linux_cooked_capture() {
+    let allocations = allocation_counter::measure(|| {
+        let ipv4 = [
+            0x45, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00,
+            0xde, 0xad, 0xbe, 0xef,
+        ];
+        let mut sll = [0u8; 16 + 32];
+        sll[..16].copy_from_slice(&[
+            0x00, 0x04, 0x00, 0x01, 0x00, 0x06, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x00, 0x00,
+            0x08, 0x00,
+        ]);
+        sll[16..].copy_from_slice(&ipv4);
+        let parsed = Packet::from_link_type(LinkType::LINUX_SLL, &sll).expect("Packet parse failed");
+        let Some(L2Packet::LinuxSll(header, L3Packet::Ipv4(_, L4Packet::Udp(_)))) = parsed.get_l2(HeaderPosition::Outer) else {panic!("Invalid packet type")};
+        assert_eq!(header.get_packet_type(), 4);
+        assert_eq!(header.get_link_layer_address_type(), 1);
+        let l2 = parsed.get_l2(HeaderPosition::Outer).expect("missing l2");
+        assert_eq!(l2.get_source_address(), &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert!(l2.get_vlans().is_empty());
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+        assert_eq!(
+            parsed.get_five_tuple(HeaderPosition::Innermost).map(|tuple| tuple.source_port),
+            Some(1234)
+        );
+
+        let mut sll2 = [0u8; 20 + 32];
+        sll2[..20].copy_from_slice(&[
+            0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x03, 0x04, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        sll2[20..].copy_from_slice(&ipv4);
+        let parsed = Packet::from_link_type(LinkType::LINUX_SLL2, &sll2).expect("Packet parse failed");
+        let Some(L2Packet::LinuxSll2(header, L3Packet::Ipv4(..))) = parsed.get_l2(HeaderPosition::Outer) else {panic!("Invalid packet type")};
+        assert_eq!(header.get_interface_index(), 3);
+        assert_eq!(header.get_arphrd_type(), 0x0304);
+        assert_eq!(header.get_packet_type(), 0);
+        let l2 = parsed.get_l2(HeaderPosition::Outer).expect("missing l2");
+        assert!(l2.get_source_address().is_empty());
+        assert_eq!(l2.get_header(), &sll2[..20]);
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+
+        assert!(matches!(
+            Packet::from_link_type(LinkType::LINUX_SLL, &sll[..10]),
+            Err(ParseError::L2Error(l2::ParseError::LinuxSll))
+        ));
+        assert!(matches!(
+            Packet::from_link_type(LinkType::LINUX_SLL2, &sll2[..18]),
+            Err(ParseError::L2Error(l2::ParseError::LinuxSll))
+        ));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_raw_ip() {
+    let allocations = allocation_counter::measure(|| {
+        let gre_packet = &[
+            0x45, 0x00, 0x00, 0x7c, 0x00, 0x0a, 0x00, 0x00, 0xff, 0x2f, 0xa7, 0x46, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x00, 0x00, 0x08, 0x00, 0x45, 0x00, 0x00, 0x64,
+            0x00, 0x0a, 0x00, 0x00, 0xff, 0x01, 0xb5, 0x89, 0x01, 0x01, 0x01, 0x01, 0x02, 0x02,
+            0x02, 0x02, 0x08, 0x00, 0xbf, 0xd4, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x03, 0xbe, 0x70, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd,
+            0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd,
+            0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd,
+            0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd,
+            0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd, 0xab, 0xcd,
+        ];
+        let parsed = Packet::from_ip(gre_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(
+            parsed.get_levels(),
+            [
+                Level::L3(None, L3Packet::Ipv4(_, L4Packet::Gre(_))),
+                Level::L3(None, L3Packet::Ipv4(_, L4Packet::Icmp(_)))
+            ]
+        ));
+        assert!(parsed.get_l2(HeaderPosition::Outer).is_none());
+        assert!(parsed.get_l2(HeaderPosition::Innermost).is_none());
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Inner).and_then(L3Packet::get_source),
+            Some(IpAddr::from([1, 1, 1, 1]))
+        );
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Outer).and_then(L3Packet::get_source),
+            Some(IpAddr::from([10, 0, 0, 1]))
+        );
+        assert_eq!(parsed.get_payload(), Some(&gre_packet[48..]));
+        assert_eq!(parsed.get_bytes(), gre_packet);
+
+        // bytes after the IP packet (like padding) aren't a part of it, but they were parsed
+        let mut padded_packet = [0; 128];
+        padded_packet[..gre_packet.len()].copy_from_slice(gre_packet);
+        let parsed = Packet::from_ip(padded_packet.as_slice()).expect("Packet parse failed");
+        assert_eq!(parsed.get_payload(), Some(&gre_packet[48..]));
+        assert_eq!(parsed.get_bytes(), padded_packet.as_slice());
+
+        let ipv6_packet = &[
+            0x60, 0x0, 0x0, 0x0, 0x0, 0x36, 0x2b, 0x40, 0x20, 0x1, 0xd, 0xb8, 0x0, 0x0,
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1, 0x20, 0x1, 0xd, 0xb8,
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x2, 0x11, 0x4,
+            0x0, 0x2, 0x0, 0x0, 0x0, 0x0, 0x20, 0x1, 0xd, 0xb8, 0x0, 0x0, 0x0, 0x0,
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x3, 0x20, 0x1, 0xd, 0xb8, 0x0, 0x0,
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x4, 0x0, 0x35, 0x0, 0x35,
+            0x0, 0xe, 0xa, 0x55, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36,
+        ];
+        let parsed = Packet::from_link_type(LinkType::RAW, ipv6_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(
+            parsed.get_levels(),
+            [Level::L3(None, L3Packet::Ipv6(_, _, L4Packet::Udp(_)))]
+        ));
+        let tuple = parsed.get_five_tuple(HeaderPosition::Innermost).expect("missing tuple");
+        assert_eq!(tuple.destination_port, 53);
+
+        assert!(matches!(
+            Packet::from_ip(&ipv6_packet[1..]),
+            Err(ParseError::InvalidIpVersion(0))
+        ));
+        assert!(matches!(
+            Packet::from_ip(&[]),
+            Err(ParseError::InvalidIpVersion(0))
+        ));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_loopback() {
+    let allocations = allocation_counter::measure(|| {
+        let ipv4 = [
+            0x45, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x7f, 0x00,
+            0x00, 0x01, 0x7f, 0x00, 0x00, 0x01, 0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00,
+            0xde, 0xad, 0xbe, 0xef,
+        ];
+        let mut ipv6 = [0u8; 40 + 8];
+        ipv6[..8].copy_from_slice(&[0x60, 0x00, 0x00, 0x00, 0x00, 0x08, 0x11, 0x40]);
+        ipv6[23] = 1;
+        ipv6[39] = 1;
+        ipv6[40..].copy_from_slice(&[0x04, 0xd2, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00]);
+
+        // the family of DLT_NULL is in the host byte order, DLT_LOOP is always big endian
+        let cases: [(LinkType, [u8; 4], &[u8]); 6] = [
+            (LinkType::NULL, 2u32.to_le_bytes(), &ipv4),
+            (LinkType::NULL, 2u32.to_be_bytes(), &ipv4),
+            (LinkType::NULL, address_families::INET6_DARWIN.to_le_bytes(), &ipv6),
+            (LinkType::NULL, address_families::INET6_LINUX.to_be_bytes(), &ipv6),
+            (LinkType::LOOP, address_families::INET6_FREEBSD.to_be_bytes(), &ipv6),
+            (LinkType::LOOP, 2u32.to_be_bytes(), &ipv4),
+        ];
+        for (link_type, family, l3) in cases {
+            let mut frame = [0u8; 4 + 48];
+            frame[..4].copy_from_slice(&family);
+            frame[4..4 + l3.len()].copy_from_slice(l3);
+            let frame = &frame[..4 + l3.len()];
+            let parsed = Packet::from_link_type(link_type, frame).expect("Packet parse failed");
+            let Some(L2Packet::Loopback(header, Some(_))) = parsed.get_l2(HeaderPosition::Outer) else {panic!("Invalid packet type")};
+            assert!(matches!(
+                header.get_family(),
+                address_families::INET
+                    | address_families::INET6_DARWIN
+                    | address_families::INET6_LINUX
+                    | address_families::INET6_FREEBSD
+            ));
+            let tuple = parsed.get_five_tuple(HeaderPosition::Innermost).expect("missing tuple");
+            assert_eq!((tuple.source_port, tuple.destination_port), (1234, 53));
+        }
+
+        let mut frame = [0u8; 8];
+        frame[..4].copy_from_slice(&17u32.to_le_bytes());
+        let parsed = Packet::from_link_type(LinkType::NULL, &frame).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [Level::L2(None, L2Packet::Loopback(_, None))]));
+        assert!(parsed.get_l3(HeaderPosition::Outer).is_none());
+        assert!(matches!(
+            Packet::from_link_type(LinkType::LOOP, &frame[..3]),
+            Err(ParseError::L2Error(l2::ParseError::Loopback))
+        ));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_llc_snap() {
+    let allocations = allocation_counter::measure(|| {
+        let mut stp_packet = [0u8; 60];
+        stp_packet[..17].copy_from_slice(&[
+            0x01, 0x80, 0xc2, 0x00, 0x00, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x00, 0x26,
+            0x42, 0x42, 0x03,
+        ]);
+        let parsed = Packet::try_from(stp_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Llc(_, _, llc, None))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert!(llc.is_stp());
+        assert!(!llc.is_snap());
+        assert_eq!(llc.get_dsap(), saps::STP);
+        assert_eq!(llc.get_control(), 0x03);
+        let l2 = parsed.get_l2(HeaderPosition::Outer).expect("missing l2");
+        assert_eq!(l2.get_header().len(), 17);
+        // the 802.3 length covers the LLC header and the 35 bytes BPDU
+        assert_eq!(l2.get_trailer(), &[0; 8]);
+        assert!(parsed.get_l3(HeaderPosition::Outer).is_none());
+
+        let mut cdp_packet = [0u8; 64];
+        cdp_packet[..22].copy_from_slice(&[
+            0x01, 0x00, 0x0c, 0xcc, 0xcc, 0xcc, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x00, 0x32,
+            0xaa, 0xaa, 0x03, 0x00, 0x00, 0x0c, 0x20, 0x00,
+        ]);
+        let parsed = Packet::try_from(cdp_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Llc(_, _, llc, None))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert!(llc.is_cdp());
+        assert_eq!(llc.get_snap_oui(), Some(0x00000c));
+        assert_eq!(llc.get_ether_type(), None);
+
+        let snap_ip_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x81, 0x00,
+            0x00, 0x0a, 0x00, 0x28, 0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00, 0x45, 0x00,
+            0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01,
+            0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad,
+            0xbe, 0xef, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let parsed = Packet::try_from(snap_ip_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(
+            parsed.get_levels(),
+            [Level::L2(None, L2Packet::Llc(_, _, _, Some(L3Packet::Ipv4(_, L4Packet::Udp(_)))))]
+        ));
+        let l2 = parsed.get_l2(HeaderPosition::Outer).expect("missing l2");
+        assert_eq!(l2.get_vlans().len(), 1);
+        assert_eq!(l2.get_trailer(), &[0; 4]);
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+        assert!(parsed.get_five_tuple(HeaderPosition::Innermost).is_some());
+
+        assert!(matches!(
+            Packet::try_from(&stp_packet[..16]),
+            Err(ParseError::L2Error(l2::ParseError::Llc))
+        ));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_mpls() {
+    let allocations = allocation_counter::measure(|| {
+        let mpls_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x88, 0x47,
+            0x00, 0x06, 0x4a, 0x40, 0x00, 0x0c, 0x81, 0x3f, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01,
+            0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+            0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        let parsed = Packet::try_from(mpls_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Mpls(labels, MplsPayload::Ipv4(_, L4Packet::Udp(_)))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[0].get_label(), 100);
+        assert_eq!(labels[0].get_traffic_class(), 5);
+        assert!(!labels[0].get_bottom_of_stack());
+        assert_eq!(labels[0].get_ttl(), 64);
+        assert_eq!(labels[1].get_label(), 200);
+        assert!(labels[1].get_bottom_of_stack());
+        assert_eq!(labels[1].get_ttl(), 63);
+        let l3 = parsed.get_l3(HeaderPosition::Outer).expect("missing l3");
+        assert_eq!(l3.get_length(), 40);
+        assert_eq!(l3.get_source(), Some(IpAddr::from([10, 0, 0, 1])));
+        assert_eq!(l3.get_l4_protocol(), Some(IpNextHeaderProtocols::Udp));
+        let l2 = parsed.get_l2(HeaderPosition::Outer).expect("missing l2");
+        assert_eq!(l2.get_trailer(), &[0; 4]);
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+        assert!(parsed.get_five_tuple(HeaderPosition::Innermost).is_some());
+
+        let options = ParseOptions {
+            max_mpls_labels: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Packet::parse_with(mpls_packet, &options),
+            Err(ParseError::L2Error(l2::ParseError::L3Error(l3::ParseError::TooManyMplsLabels)))
+        ));
+        // the stack is cut before the bottom label
+        assert!(matches!(
+            Packet::try_from(&mpls_packet[..20]),
+            Err(ParseError::L2Error(l2::ParseError::L3Error(l3::ParseError::Mpls)))
+        ));
+
+        let gre_mpls_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x50, 0x00, 0x00, 0x00, 0x00, 0x40, 0x2f, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0x00, 0x00, 0x88, 0x47, 0x00, 0x01, 0x01, 0x40,
+            0x60, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x11, 0x40, 0xfe, 0x80, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0xfe, 0x80, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x35,
+            0x04, 0xd2, 0x00, 0x0c, 0x00, 0x00, 0xca, 0xfe, 0xba, 0xbe,
+        ];
+        let parsed = Packet::try_from(gre_mpls_packet.as_slice()).expect("Packet parse failed");
+        let [_, Level::L3(None, L3Packet::Mpls(labels, MplsPayload::Ipv6(_, _, L4Packet::Udp(_))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(labels[0].get_label(), 16);
+        let inner_l3 = parsed.get_l3(HeaderPosition::Inner).expect("missing inner l3");
+        assert_eq!(
+            inner_l3.get_destination(),
+            Some(IpAddr::from([0xfe80, 0, 0, 0, 0, 0, 0, 2]))
+        );
+        assert_eq!(parsed.get_payload(), Some([0xca, 0xfe, 0xba, 0xbe].as_slice()));
+
+        let pseudowire_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x88, 0x47,
+            0x00, 0x01, 0x01, 0x40, 0x00, 0x00, 0x00, 0x07, 0x00, 0xaa, 0xbb, 0xcc, 0xdd, 0xee,
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01,
+            0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+            0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::try_from(pseudowire_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Mpls(_, MplsPayload::Unknown(payload))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(payload.len(), 50);
+        let options = ParseOptions {
+            mpls_pseudowire: MplsPseudowire::Ethernet { control_word: true },
+            ..Default::default()
+        };
+        let parsed = Packet::parse_with(pseudowire_packet, &options).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Mpls(_, MplsPayload::Ethernet(Some(0x07), frame)))), Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Udp(_))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(frame.get_ethertype(), EtherTypes::Ipv4);
+        assert!(parsed.get_l4(HeaderPosition::Outer).is_none());
+        // the flow inside the pseudowire is reachable
+        let Some(tuple) = parsed.get_five_tuple(HeaderPosition::Innermost) else {panic!("missing five tuple")};
+        assert_eq!(tuple.source_ip, IpAddr::from([10, 0, 0, 1]));
+        assert_eq!((tuple.source_port, tuple.destination_port), (1234, 53));
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+        let l2 = parsed.get_l2(HeaderPosition::Outer).expect("missing l2");
+        assert!(l2.get_trailer().is_empty());
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_pppoe() {
+    let allocations = allocation_counter::measure(|| {
+        let mut padi_packet = [0u8; 60];
+        padi_packet[..32].copy_from_slice(&[
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x88, 0x63,
+            0x11, 0x09, 0x00, 0x00, 0x00, 0x0c, 0x01, 0x01, 0x00, 0x00, 0x01, 0x03, 0x00, 0x04,
+            0xde, 0xad, 0xbe, 0xef,
+        ]);
+        let parsed = Packet::try_from(padi_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Pppoe(pppoe, PppoePayload::Discovery)))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(pppoe.get_version(), 1);
+        assert_eq!(pppoe.get_type(), 1);
+        assert_eq!(pppoe.get_code(), codes::PADI);
+        assert!(pppoe.is_discovery());
+        assert_eq!(pppoe.get_tags().count(), 2);
+        assert_eq!(pppoe.get_tags().get(tag_types::SERVICE_NAME), Some([].as_slice()));
+        assert_eq!(
+            pppoe.get_tags().get(tag_types::HOST_UNIQ),
+            Some([0xde, 0xad, 0xbe, 0xef].as_slice())
+        );
+        let l2 = parsed.get_l2(HeaderPosition::Outer).expect("missing l2");
+        assert_eq!(l2.get_trailer(), &[0; 28]);
+
+        let session_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x88, 0x64,
+            0x11, 0x00, 0x12, 0x34, 0x00, 0x22, 0x00, 0x21, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01,
+            0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+            0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        let parsed = Packet::try_from(session_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Pppoe(pppoe, PppoePayload::Ipv4(ppp, _, L4Packet::Udp(_)))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(pppoe.get_session_id(), 0x1234);
+        assert_eq!(pppoe.get_code(), codes::SESSION);
+        assert_eq!(ppp.get_protocol(), 0x0021);
+        let l3 = parsed.get_l3(HeaderPosition::Outer).expect("missing l3");
+        assert_eq!(l3.get_length(), 40);
+        assert_eq!(l3.get_destination(), Some(IpAddr::from([10, 0, 0, 2])));
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+        assert!(parsed.get_five_tuple(HeaderPosition::Innermost).is_some());
+
+        // the PPPoE payload is longer than the IP packet in it
+        let gre_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x88, 0x64,
+            0x11, 0x00, 0x12, 0x34, 0x00, 0x3e, 0x00, 0x21, 0x45, 0x00, 0x00, 0x38, 0x00, 0x00,
+            0x00, 0x00, 0x40, 0x2f, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+            0x00, 0x00, 0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11,
+            0x00, 0x00, 0x01, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x04, 0xd2, 0x00, 0x35,
+            0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef, 0xff, 0xff, 0xff, 0xff,
+        ];
+        let parsed = Packet::try_from(gre_packet.as_slice()).expect("Packet parse failed");
+        let [_, Level::L3(None, L3Packet::Ipv4(_, L4Packet::Udp(_)))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Inner).and_then(L3Packet::get_source),
+            Some(IpAddr::from([1, 1, 1, 1]))
+        );
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+
+        let lcp_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x88, 0x64,
+            0x11, 0x00, 0x12, 0x34, 0x00, 0x10, 0xc0, 0x21, 0x01, 0x01, 0x00, 0x0e, 0x01, 0x04,
+            0x05, 0xd4, 0x05, 0x06, 0x00, 0x01, 0x02, 0x03,
+        ];
+        let parsed = Packet::try_from(lcp_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Pppoe(_, PppoePayload::Control(_, PppControl::Lcp(lcp)))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(lcp.get_code(), control_codes::CONFIGURE_REQUEST);
+        assert_eq!(lcp.get_identifier(), 1);
+        let mut options = lcp.get_options().expect("missing options");
+        let mru = options.next().expect("missing mru");
+        assert_eq!((mru.option_type, mru.value), (1, [0x05, 0xd4].as_slice()));
+        let magic = options.next().expect("missing magic number");
+        assert_eq!(magic.value, &[0x00, 0x01, 0x02, 0x03]);
+        assert!(options.next().is_none());
+        assert!(lcp.get_magic_number().is_none());
+        assert!(parsed.get_l4(HeaderPosition::Outer).is_none());
+
+        let pap_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x88, 0x64,
+            0x11, 0x00, 0x12, 0x34, 0x00, 0x10, 0xc0, 0x23, 0x01, 0x02, 0x00, 0x0e, 0x04, b'u',
+            b's', b'e', b'r', 0x04, b'p', b'a', b's', b's',
+        ];
+        let parsed = Packet::try_from(pap_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Pppoe(_, PppoePayload::Control(_, PppControl::Pap(pap)))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(pap.get_peer_id(), Some(b"user".as_slice()));
+        assert_eq!(pap.get_password(), Some(b"pass".as_slice()));
+        assert!(pap.get_message().is_none());
+
+        let chap_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x88, 0x64,
+            0x11, 0x00, 0x12, 0x34, 0x00, 0x0d, 0xc2, 0x23, 0x01, 0x03, 0x00, 0x0b, 0x04, 0x01,
+            0x02, 0x03, 0x04, b'r', b'a',
+        ];
+        let parsed = Packet::try_from(chap_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Pppoe(_, PppoePayload::Control(_, PppControl::Chap(chap)))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(chap.get_identifier(), 3);
+        assert_eq!(chap.get_value(), Some([0x01, 0x02, 0x03, 0x04].as_slice()));
+        assert_eq!(chap.get_name(), Some(b"ra".as_slice()));
+
+        // a session packet without a PPP header
+        let mut empty_session_packet = session_packet.to_owned();
+        empty_session_packet[19] = 0;
+        assert!(matches!(
+            Packet::try_from(empty_session_packet.as_slice()),
+            Err(ParseError::L2Error(l2::ParseError::L3Error(l3::ParseError::Ppp)))
+        ));
+        assert!(matches!(
+            Packet::try_from(&session_packet[..19]),
+            Err(ParseError::L2Error(l2::ParseError::L3Error(l3::ParseError::Pppoe)))
+        ));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_vxlan() {
+    let allocations = allocation_counter::measure(|| {
+        let vxlan_packet = [
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x52, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0xc3, 0x50, 0x12, 0xb5, 0x00, 0x3e, 0x00, 0x00,
+            0x08, 0x00, 0x00, 0x00, 0x12, 0x34, 0x56, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01,
+            0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+            0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::try_from(vxlan_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(..)), Level::L2(Some(Encapsulation::Vxlan(vxlan)), L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Udp(_))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert!(vxlan.is_vni_valid());
+        assert_eq!(vxlan.get_vni(), 0x123456);
+        assert_eq!(
+            parsed.get_l2(HeaderPosition::Inner).map(|l2| l2.get_source_address()),
+            Some([0x02, 0x00, 0x00, 0x00, 0x00, 0x02].as_slice())
+        );
+        assert_eq!(
+            parsed.get_l2(HeaderPosition::Outer).map(|l2| l2.get_source_address()),
+            Some([0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb].as_slice())
+        );
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Outer).and_then(|l3| l3.get_source()),
+            Some(IpAddr::from([192, 168, 0, 1]))
+        );
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Inner).and_then(|l3| l3.get_source()),
+            Some(IpAddr::from([10, 0, 0, 1]))
+        );
+        let Some(inner_tuple) = parsed.get_five_tuple(HeaderPosition::Innermost) else {panic!("missing five tuple")};
+        assert_eq!((inner_tuple.source_port, inner_tuple.destination_port), (1234, 53));
+        let Some(outer_tuple) = parsed.get_five_tuple(HeaderPosition::Outer) else {panic!("missing five tuple")};
+        assert_eq!(outer_tuple.destination_port, 4789);
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+        assert_eq!(parsed.get_bytes(), vxlan_packet.as_slice());
+
+        let parsed = Packet::from_ip(&vxlan_packet[14..]).expect("Packet parse failed");
+        assert!(matches!(
+            parsed.get_levels(),
+            [Level::L3(None, _), Level::L2(Some(Encapsulation::Vxlan(_)), _)]
+        ));
+        assert!(parsed.get_l2(HeaderPosition::Outer).is_none());
+        assert!(parsed.get_l2(HeaderPosition::Inner).is_some());
+
+        let options = ParseOptions {
+            max_tunnel_depth: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Packet::parse_with(vxlan_packet.as_slice(), &options),
+            Err(ParseError::TunnelDepthExceeded)
+        ));
+        let options = ParseOptions {
+            vxlan_ports: smallvec![8472],
+            ..Default::default()
+        };
+        let parsed = Packet::parse_with(vxlan_packet.as_slice(), &options).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [Level::L2(None, _)]));
+        assert!(parsed.get_l2(HeaderPosition::Inner).is_none());
+        let mut custom_port_packet = vxlan_packet;
+        custom_port_packet[36..38].copy_from_slice(&8472u16.to_be_bytes());
+        let parsed =
+            Packet::parse_with(custom_port_packet.as_slice(), &options).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [_, Level::L2(Some(_), _)]));
+
+        // udp payloads on the VXLAN port that aren't VXLAN are kept as payloads
+        let parsed = Packet::parse_with(&custom_port_packet[..48], &options).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [Level::L2(None, _)]));
+        assert_eq!(parsed.get_payload(), Some(&custom_port_packet[42..48]));
+        let parsed = Packet::try_from(&vxlan_packet[..60]).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [Level::L2(None, _)]));
+        let mut not_vxlan_packet = vxlan_packet;
+        not_vxlan_packet[42] = 0x00;
+        let parsed = Packet::try_from(not_vxlan_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [Level::L2(None, _)]));
+        assert_eq!(parsed.get_payload(), Some(&not_vxlan_packet[42..]));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_geneve() {
+    let allocations = allocation_counter::measure(|| {
+        let ethernet_geneve_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0xc3, 0x50, 0x17, 0xc1, 0x00, 0x46, 0x00, 0x00,
+            0x02, 0x40, 0x65, 0x58, 0x00, 0x00, 0x64, 0x00, 0x01, 0x08, 0x81, 0x01, 0xaa, 0xbb,
+            0xcc, 0xdd, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x02,
+            0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00,
+            0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c,
+            0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed =
+            Packet::try_from(ethernet_geneve_packet.as_slice()).expect("Packet parse failed");
+        let [_, Level::L2(Some(Encapsulation::Geneve(geneve)), L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Udp(_))))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(geneve.get_version(), 0);
+        assert_eq!(geneve.get_options_length(), 2);
+        assert!(!geneve.get_oam());
+        assert!(geneve.get_critical());
+        assert_eq!(geneve.get_protocol_type(), l2::TRANSPARENT_ETHERNET_BRIDGING);
+        assert_eq!(geneve.get_vni(), 100);
+        let mut options = geneve.get_options();
+        let option = options.next().expect("missing option");
+        assert_eq!(option.class, 0x0108);
+        assert_eq!(option.option_type, 0x81);
+        assert!(option.is_critical());
+        assert_eq!(option.data, &[0xaa, 0xbb, 0xcc, 0xdd]);
+        assert!(options.next().is_none());
+        assert_eq!(
+            parsed.get_l2(HeaderPosition::Inner).map(|l2| l2.get_source_address()),
+            Some([0x02, 0x00, 0x00, 0x00, 0x00, 0x02].as_slice())
+        );
+        let Some(tuple) = parsed.get_five_tuple(HeaderPosition::Innermost) else {panic!("missing five tuple")};
+        assert_eq!(tuple.source_ip, IpAddr::from([10, 0, 0, 1]));
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+
+        let ip_geneve_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x44, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0xc3, 0x50, 0x17, 0xc1, 0x00, 0x30, 0x00, 0x00,
+            0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01,
+            0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+            0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::try_from(ip_geneve_packet.as_slice()).expect("Packet parse failed");
+        let [_, Level::L3(Some(Encapsulation::Geneve(geneve)), L3Packet::Ipv4(_, L4Packet::Udp(_)))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(geneve.get_vni(), 200);
+        assert_eq!(geneve.get_options().count(), 0);
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Inner).and_then(|l3| l3.get_destination()),
+            Some(IpAddr::from([10, 0, 0, 2]))
+        );
+        assert!(parsed.get_l2(HeaderPosition::Inner).is_none());
+
+        let options = ParseOptions {
+            max_options: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Packet::parse_with(ethernet_geneve_packet.as_slice(), &options),
+            Err(ParseError::TooManyGeneveOptions)
+        ));
+        let options = ParseOptions {
+            max_options: Some(1),
+            ..Default::default()
+        };
+        assert!(Packet::parse_with(ethernet_geneve_packet.as_slice(), &options).is_ok());
+
+        // udp payloads on the Geneve port that aren't Geneve are kept as payloads,
+        // like when the options length is larger than the packet
+        let parsed = Packet::try_from(&ip_geneve_packet[..48]).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [Level::L2(None, _)]));
+        assert_eq!(parsed.get_payload(), Some(&ip_geneve_packet[42..48]));
+        let mut unknown_version_packet = *ip_geneve_packet;
+        unknown_version_packet[42] = 0x40;
+        let parsed = Packet::try_from(unknown_version_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [Level::L2(None, _)]));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_tunnel_limits() {
+    let allocations = allocation_counter::measure(|| {
+        // the inner frame has 2 vlan tags
+        let vxlan_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0xc3, 0x50, 0x12, 0xb5, 0x00, 0x46, 0x00, 0x00,
+            0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x64, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x81, 0x00, 0x00, 0x0a, 0x81, 0x00, 0x00, 0x14,
+            0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00,
+            0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c,
+            0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::try_from(vxlan_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [_, Level::L2(Some(Encapsulation::Vxlan(_)), _)]));
+
+        // the limits apply to the inner frame, it isn't treated as a non VXLAN payload
+        let options = ParseOptions {
+            max_vlan_tags: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Packet::parse_with(vxlan_packet.as_slice(), &options),
+            Err(ParseError::L2Error(l2::ParseError::TooManyVlans))
+        ));
+
+        // the same inner frame, in Geneve
+        let geneve_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0xc3, 0x50, 0x17, 0xc1, 0x00, 0x46, 0x00, 0x00,
+            0x00, 0x00, 0x65, 0x58, 0x00, 0x00, 0x64, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x81, 0x00, 0x00, 0x0a, 0x81, 0x00, 0x00, 0x14,
+            0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00,
+            0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c,
+            0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::try_from(geneve_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(parsed.get_levels(), [_, Level::L2(Some(Encapsulation::Geneve(_)), _)]));
+        assert!(matches!(
+            Packet::parse_with(geneve_packet.as_slice(), &options),
+            Err(ParseError::L2Error(l2::ParseError::TooManyVlans))
+        ));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_nested_tunnels() {
+    let allocations = allocation_counter::measure(|| {
+        let gre_in_gre_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x50, 0x00, 0x00, 0x00, 0x00, 0x40, 0x2f, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0x00, 0x00, 0x08, 0x00, 0x45, 0x00, 0x00, 0x38,
+            0x00, 0x00, 0x00, 0x00, 0x40, 0x2f, 0x00, 0x00, 0xac, 0x10, 0x00, 0x01, 0xac, 0x10,
+            0x00, 0x02, 0x00, 0x00, 0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00,
+            0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2,
+            0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::try_from(gre_in_gre_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(_, L4Packet::Gre(_)))), Level::L3(None, L3Packet::Ipv4(_, L4Packet::Gre(_))), Level::L3(None, L3Packet::Ipv4(_, L4Packet::Udp(_)))] = parsed.get_levels() else {panic!("Invalid packet type")};
+        let source_at = |position| parsed.get_l3(position).and_then(L3Packet::get_source);
+        assert_eq!(source_at(HeaderPosition::Outer), Some(IpAddr::from([192, 168, 0, 1])));
+        assert_eq!(source_at(HeaderPosition::Inner), Some(IpAddr::from([172, 16, 0, 1])));
+        assert_eq!(source_at(HeaderPosition::Nth(2)), Some(IpAddr::from([10, 0, 0, 1])));
+        assert_eq!(source_at(HeaderPosition::Innermost), Some(IpAddr::from([10, 0, 0, 1])));
+        assert_eq!(source_at(HeaderPosition::Nth(3)), None);
+        // the innermost l2 is the outer ethernet, as the inner levels are L3 only
+        assert!(parsed.get_l2(HeaderPosition::Nth(1)).is_none());
+        assert_eq!(
+            parsed.get_l2(HeaderPosition::Innermost),
+            parsed.get_l2(HeaderPosition::Outer)
+        );
+        let Some(tuple) = parsed.get_five_tuple(HeaderPosition::Innermost) else {panic!("missing five tuple")};
+        assert_eq!(tuple.destination_port, 53);
+        assert!(parsed.get_five_tuple(HeaderPosition::Inner).is_none());
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+        assert_eq!(parsed.get_bytes(), gre_in_gre_packet.as_slice());
+
+        let options = ParseOptions {
+            max_tunnel_depth: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Packet::parse_with(gre_in_gre_packet.as_slice(), &options),
+            Err(ParseError::TunnelDepthExceeded)
+        ));
+
+        let gre_in_vxlan_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x6a, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0xc3, 0x50, 0x12, 0xb5, 0x00, 0x56, 0x00, 0x00,
+            0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2a, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x08, 0x00, 0x45, 0x00, 0x00, 0x38, 0x00, 0x00,
+            0x00, 0x00, 0x40, 0x2f, 0x00, 0x00, 0xac, 0x10, 0x00, 0x01, 0xac, 0x10, 0x00, 0x02,
+            0x00, 0x00, 0x08, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11,
+            0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35,
+            0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::try_from(gre_in_vxlan_packet.as_slice()).expect("Packet parse failed");
+        let [Level::L2(None, _), Level::L2(Some(Encapsulation::Vxlan(vxlan)), L2Packet::Ethernet(..)), Level::L3(None, _)] = parsed.get_levels() else {panic!("Invalid packet type")};
+        assert_eq!(vxlan.get_vni(), 42);
+        assert!(matches!(
+            parsed.get_encapsulation(HeaderPosition::Innermost),
+            Some(Encapsulation::Vxlan(_))
+        ));
+        assert!(parsed.get_encapsulation(HeaderPosition::Nth(2)).is_none());
+        assert_eq!(
+            parsed.get_l2(HeaderPosition::Innermost).map(|l2| l2.get_source_address()),
+            Some([0x02, 0x00, 0x00, 0x00, 0x00, 0x02].as_slice())
+        );
+        assert!(matches!(
+            parsed.get_l4(HeaderPosition::Inner),
+            Some(L4Packet::Gre(_))
+        ));
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Innermost).and_then(L3Packet::get_destination),
+            Some(IpAddr::from([10, 0, 0, 2]))
+        );
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}
+
+#[test]
+fn test_ip_in_ip() {
+    let allocations = allocation_counter::measure(|| {
+        let ipv4_in_ipv4_packet = &[
+            0x45, 0x00, 0x00, 0x34, 0x00, 0x00, 0x00, 0x00, 0x40, 0x04, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00,
+            0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2,
+            0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::from_ip(ipv4_in_ipv4_packet).expect("Packet parse failed");
+        assert!(matches!(
+            parsed.get_levels(),
+            [
+                Level::L3(None, L3Packet::Ipv4(..)),
+                Level::L3(None, L3Packet::Ipv4(_, L4Packet::Udp(_)))
+            ]
+        ));
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Inner).and_then(L3Packet::get_source),
+            Some(IpAddr::from([10, 0, 0, 1]))
+        );
+        let Some(tuple) = parsed.get_five_tuple(HeaderPosition::Innermost) else {panic!("missing five tuple")};
+        assert_eq!(tuple.source_ip, IpAddr::from([10, 0, 0, 1]));
+        assert_eq!(tuple.protocol, IpNextHeaderProtocols::Udp);
+        // the outer header has no ports
+        assert!(parsed.get_five_tuple(HeaderPosition::Outer).is_none());
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Outer).and_then(L3Packet::get_l4_protocol),
+            Some(IpNextHeaderProtocols::Ipv4)
+        );
+
+        let ipv6_in_ipv4_packet = &[
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x00, 0x40, 0x29, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02, 0x60, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x11, 0x40,
+            0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x01, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad,
+            0xbe, 0xef,
+        ];
+        let parsed = Packet::try_from(ipv6_in_ipv4_packet.as_slice()).expect("Packet parse failed");
+        assert!(matches!(
+            parsed.get_levels(),
+            [
+                Level::L2(None, L2Packet::Ethernet(_, _, L3Packet::Ipv4(..))),
+                Level::L3(None, L3Packet::Ipv6(_, _, L4Packet::Udp(_)))
+            ]
+        ));
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Innermost).and_then(L3Packet::get_destination),
+            Some(IpAddr::from([0x2001, 0xdb8, 0, 0, 0, 0, 0, 2]))
+        );
+        assert_eq!(parsed.get_payload(), Some([0xde, 0xad, 0xbe, 0xef].as_slice()));
+
+        let ipv4_in_ipv6_packet = &[
+            0x60, 0x00, 0x00, 0x00, 0x00, 0x20, 0x04, 0x40, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x20, 0x01, 0x0d, 0xb8,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0b, 0x45, 0x00,
+            0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01,
+            0x0a, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad,
+            0xbe, 0xef,
+        ];
+        let parsed = Packet::from_ip(ipv4_in_ipv6_packet).expect("Packet parse failed");
+        assert!(matches!(
+            parsed.get_levels(),
+            [
+                Level::L3(None, L3Packet::Ipv6(..)),
+                Level::L3(None, L3Packet::Ipv4(_, L4Packet::Udp(_)))
+            ]
+        ));
+
+        let ipv6_in_ipv6_packet = &[
+            0x60, 0x00, 0x00, 0x00, 0x00, 0x34, 0x29, 0x40, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x20, 0x01, 0x0d, 0xb8,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0b, 0x60, 0x00,
+            0x00, 0x00, 0x00, 0x0c, 0x11, 0x40, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x04, 0xd2, 0x00, 0x35,
+            0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let parsed = Packet::from_ip(ipv6_in_ipv6_packet).expect("Packet parse failed");
+        assert!(matches!(
+            parsed.get_levels(),
+            [
+                Level::L3(None, L3Packet::Ipv6(..)),
+                Level::L3(None, L3Packet::Ipv6(_, _, L4Packet::Udp(_)))
+            ]
+        ));
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Outer).and_then(L3Packet::get_source),
+            Some(IpAddr::from([0x2001, 0xdb8, 0, 0, 0, 0, 0, 0xa]))
+        );
+        assert_eq!(
+            parsed.get_l3(HeaderPosition::Inner).and_then(L3Packet::get_source),
+            Some(IpAddr::from([0x2001, 0xdb8, 0, 0, 0, 0, 0, 1]))
+        );
+
+        assert!(matches!(
+            Packet::from_ip(&ipv4_in_ipv4_packet[..30]),
+            Err(ParseError::L3Error(l3::ParseError::IPv4))
+        ));
+    });
+    assert_eq!(allocations.count_total, 0, "allocations detected");
+}